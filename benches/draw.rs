@@ -0,0 +1,145 @@
+//! How long a frame takes to work out on a 320x240 screen in 16x16
+//! tiles with 64 commands, with a renderer that draws nothing so only
+//! the display list's own work is timed. The loop `draw` ran before
+//! commands were indexed by tile is timed too, as a baseline.
+//!
+//! Run with `cargo bench`.
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use blitty::{tile_words, Below, BoundingBox, Command, DisplayList, DisplayListError, Point, Renderer, RendererError, Rgb, ShapeStyle};
+
+const WIDTH: u32 = 320;
+const HEIGHT: u32 = 240;
+const TILE: u32 = 16;
+const FRAMES: u32 = 1000;
+
+type Scene = DisplayList<64, Rgb, { tile_words(WIDTH, HEIGHT, TILE, TILE) }>;
+
+struct NullRenderer;
+
+impl Renderer for NullRenderer {
+    fn width(&self) -> u32 {
+        WIDTH
+    }
+
+    fn height(&self) -> u32 {
+        HEIGHT
+    }
+
+    fn chunk_size(&self) -> (u32, u32) {
+        (TILE, TILE)
+    }
+
+    fn set_chunk(&mut self, _x: u32, _y: u32) -> Result<(), RendererError> {
+        Ok(())
+    }
+
+    fn clear(&mut self) -> Result<(), RendererError> {
+        Ok(())
+    }
+
+    fn draw(&mut self, _command: &Command) -> Result<(), RendererError> {
+        Ok(())
+    }
+
+    fn draw_blended(&mut self, _command: &Command, _below: Below) -> Result<(), RendererError> {
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> Result<(), RendererError> {
+        Ok(())
+    }
+}
+
+/// A background and a grid of buttons with a circle on each.
+fn scene() -> Result<Scene, DisplayListError> {
+    let mut list = Scene::new();
+    list.set(0, Command::new_rect(BoundingBox::new(0, 0, WIDTH as i32, HEIGHT as i32), Rgb::BLUE))?;
+
+    for i in 0..63i32 {
+        let (x, y) = (8 + (i % 9) * 34, 8 + (i / 9) * 32);
+        let command = if i % 2 == 0 {
+            Command::new_rect(BoundingBox::new(x, y, x + 28, y + 20), Rgb::GRAY)
+        } else {
+            Command::new_circle(Point::new(x + 14, y + 10), 10, ShapeStyle::fill(Rgb::WHITE))
+        };
+        list.set(i as usize + 1, command)?;
+    }
+
+    Ok(list)
+}
+
+/// The loop `draw` ran for every tile before commands were indexed by
+/// tile, kept as a baseline: a pass over every slot for change, one
+/// for occlusion and one for drawing, each testing the command against
+/// the tile.
+fn old_loop(list: &Scene, renderer: &mut NullRenderer) -> Result<usize, DisplayListError> {
+    let mut found = 0;
+
+    for x in (0..WIDTH).step_by(TILE as usize) {
+        for y in (0..HEIGHT).step_by(TILE as usize) {
+            let tile = BoundingBox::new(x as i32, y as i32, (x + TILE) as i32, (y + TILE) as i32);
+            renderer.set_chunk(x, y)?;
+
+            for _pass in 0..3 {
+                for slot in 0..Scene::LENGTH {
+                    let command = list.get(slot)?;
+                    if command.intersects(&tile)? {
+                        found += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(found)
+}
+
+fn time(name: &str, mut frame: impl FnMut(u32) -> Result<(), DisplayListError>) -> Result<(), DisplayListError> {
+    let start = Instant::now();
+    for i in 0..FRAMES {
+        frame(i)?;
+    }
+    let per_frame = start.elapsed() / FRAMES;
+
+    println!("{name:<24} {:>10.1?} per frame", per_frame);
+    Ok(())
+}
+
+fn main() -> Result<(), DisplayListError> {
+    let mut renderer = NullRenderer;
+
+    let mut list = scene()?;
+    smol::block_on(list.draw(&mut renderer))?;
+    time("nothing changed", |_| {
+        smol::block_on(list.draw(&mut renderer))?;
+        Ok(())
+    })?;
+
+    let mut list = scene()?;
+    smol::block_on(list.draw(&mut renderer))?;
+    time("one command moved", |i| {
+        let x = (i % (WIDTH - 20)) as i32;
+        list.update(63, Command::new_rect(BoundingBox::new(x, 100, x + 20, 120), Rgb::RED))?;
+        smol::block_on(list.draw(&mut renderer))?;
+        Ok(())
+    })?;
+
+    let mut list = scene()?;
+    time("everything changed", |i| {
+        let color = if i % 2 == 0 { Rgb::BLUE } else { Rgb::BLACK };
+        list.update(0, Command::new_rect(BoundingBox::new(0, 0, WIDTH as i32, HEIGHT as i32), color))?;
+        smol::block_on(list.draw(&mut renderer))?;
+        Ok(())
+    })?;
+
+    let list = scene()?;
+    time("old loop, every tile", |_| {
+        black_box(old_loop(black_box(&list), &mut renderer)?);
+        Ok(())
+    })?;
+
+    Ok(())
+}
@@ -0,0 +1,177 @@
+use core::cmp::min;
+
+use super::*;
+
+/// A color with an alpha channel, 255 being opaque.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Rgba {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Rgba {
+    pub fn new( r: u8, g: u8, b: u8, a: u8 ) -> Self {
+        Self { r, g, b, a }
+    }
+
+    pub fn rgb(&self) -> Rgb {
+        Rgb { r: self.r, g: self.g, b: self.b }
+    }
+}
+
+impl From<Rgb> for Rgba {
+    fn from(value: Rgb) -> Self {
+        Rgba { r: value.r, g: value.g, b: value.b, a: 255 }
+    }
+}
+
+/// How a command's colors are combined with what is already drawn
+/// beneath it. The result is then mixed with what was beneath by the
+/// command's opacity.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum BlendMode {
+    /// Paint over what is beneath.
+    #[default]
+    SourceOver,
+    /// Darken what is beneath, white leaves it alone.
+    Multiply,
+    /// Lighten what is beneath, black leaves it alone.
+    Additive,
+    /// Flip the bits of what is beneath that are set in the color.
+    Xor,
+    /// Invert what is beneath whatever the color, like a selection
+    /// bar over text on a one bit panel.
+    Invert,
+}
+
+impl BlendMode {
+    /// `src` drawn over `dst` at `opacity / 255`.
+    pub(crate) fn blend<C: Color>(&self, opacity: u8, src: C, dst: C) -> C {
+        if *self == BlendMode::SourceOver && opacity == 255 {
+            return src;
+        }
+
+        let (src, dst): (Rgb, Rgb) = (src.into(), dst.into());
+        let channel = |s: u8, d: u8| {
+            let blended = match self {
+                BlendMode::SourceOver => s,
+                BlendMode::Multiply => (s as u32 * d as u32 / 255) as u8,
+                BlendMode::Additive => s.saturating_add(d),
+                BlendMode::Xor => s ^ d,
+                BlendMode::Invert => !d,
+            };
+
+            let (blended, d, a) = (blended as u32, d as u32, opacity as u32);
+            ((blended * a + d * (255 - a) + 127) / 255) as u8
+        };
+
+        C::from(Rgb {
+            r: channel(src.r, dst.r),
+            g: channel(src.g, dst.g),
+            b: channel(src.b, dst.b),
+        })
+    }
+
+    /// `src` drawn over `dst` on a one bit panel, where `on` says which
+    /// colors light a pixel. Inverting and xoring flip the pixel beneath
+    /// rather than each channel of its color.
+    pub(crate) fn blend_bits<C: Color>(&self, opacity: u8, src: C, dst: C, on: impl Fn(C) -> bool) -> C {
+        match (self, opacity) {
+            (BlendMode::Invert, 255) => mono_color(!on(dst)),
+            (BlendMode::Xor, 255) => mono_color(on(dst) != on(src)),
+            _ => self.blend(opacity, src, dst),
+        }
+    }
+}
+
+/// White for on and black for off, which every color turns back into
+/// its own on and off.
+pub(crate) fn mono_color<C: Color>(on: bool) -> C {
+    if on {
+        C::from(Rgb { r: 255, g: 255, b: 255 })
+    } else {
+        C::from(Rgb { r: 0, g: 0, b: 0 })
+    }
+}
+
+/// The commands already drawn in a chunk, bottom first, for a command
+/// that blends with them. They are looked up by slot in the display
+/// list rather than copied out of it.
+#[derive(Clone, Copy)]
+pub struct Below<'a, C = Rgb> {
+    commands: &'a [Command<C>],
+    slots: &'a [usize],
+}
+
+impl<'a, C> Below<'a, C> {
+    /// The commands at `slots` of `commands`, bottom first.
+    pub fn new(commands: &'a [Command<C>], slots: &'a [usize]) -> Self {
+        Self { commands, slots }
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    /// The commands from the bottom up.
+    pub fn iter(&self) -> impl Iterator<Item = &'a Command<C>> + 'a {
+        let commands = self.commands;
+        self.slots.iter().map(move |&slot| &commands[slot])
+    }
+}
+
+/// How many pixels of a row are composited at a time, one per bit of
+/// a `u64`.
+const BACKDROP_WIDTH: usize = 64;
+
+/// Call `f` with every pixel `command` paints in `clip` along with
+/// its color and the color beneath it, which is `below`, the commands
+/// already drawn in the chunk from the bottom up, on a black
+/// background. Each of those is laid over the ones before it with
+/// `mix`, as the renderer would have drawn it.
+///
+/// Renderers can't read back what they have drawn, so what is beneath
+/// is worked out again a row at a time.
+pub(crate) fn composite<C: Color>(
+    command: &Command<C>,
+    below: Below<'_, C>,
+    clip: &BoundingBox,
+    mix: impl Fn(BlendMode, u8, C, C) -> C,
+    mut f: impl FnMut(i32, i32, C, C),
+) {
+    for y in clip.y1..clip.y2 {
+        for x1 in (clip.x1..clip.x2).step_by(BACKDROP_WIDTH) {
+            let row = BoundingBox::new(x1, y, min(x1 + BACKDROP_WIDTH as i32, clip.x2), y + 1);
+            let mut backdrop = [C::from(Rgb { r: 0, g: 0, b: 0 }); BACKDROP_WIDTH];
+
+            // Strokes can paint a pixel more than once where segments
+            // meet, but each command blends with it only once.
+            for beneath in below.iter() {
+                let mut seen = 0u64;
+                beneath.pixels(&row, |x, _, rgb| {
+                    let bit = 1 << (x - x1);
+                    if seen & bit == 0 {
+                        seen |= bit;
+                        let dst = &mut backdrop[(x - x1) as usize];
+                        *dst = mix(beneath.blend, beneath.opacity, rgb, *dst);
+                    }
+                });
+            }
+
+            let mut seen = 0u64;
+            command.pixels(&row, |x, y, rgb| {
+                let bit = 1 << (x - x1);
+                if seen & bit == 0 {
+                    seen |= bit;
+                    f(x, y, rgb, backdrop[(x - x1) as usize]);
+                }
+            });
+        }
+    }
+}
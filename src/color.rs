@@ -0,0 +1,162 @@
+use core::fmt;
+
+use embedded_graphics::pixelcolor::{
+    BinaryColor, Gray2, Gray4, Gray8, GrayColor, IntoStorage, Rgb565, Rgb888, RgbColor,
+};
+
+use super::*;
+
+/// A color commands can be drawn in. Use the panel's own color so a
+/// display list stores no more than the panel can show and nothing is
+/// converted when it is drawn. Gradients, blending and dithering work
+/// on `Rgb` and convert back.
+pub trait Color: Copy + PartialEq + Eq + fmt::Debug + From<Rgb> + Into<Rgb> {}
+
+impl<T> Color for T where T: Copy + PartialEq + Eq + fmt::Debug + From<Rgb> + Into<Rgb> {}
+
+impl Rgb {
+    pub const BLACK: Rgb = Rgb::from_rgb(0, 0, 0);
+    pub const WHITE: Rgb = Rgb::from_rgb(255, 255, 255);
+    pub const GRAY: Rgb = Rgb::from_rgb(128, 128, 128);
+    pub const RED: Rgb = Rgb::from_rgb(255, 0, 0);
+    pub const GREEN: Rgb = Rgb::from_rgb(0, 255, 0);
+    pub const BLUE: Rgb = Rgb::from_rgb(0, 0, 255);
+    pub const YELLOW: Rgb = Rgb::from_rgb(255, 255, 0);
+    pub const CYAN: Rgb = Rgb::from_rgb(0, 255, 255);
+    pub const MAGENTA: Rgb = Rgb::from_rgb(255, 0, 255);
+
+    /// `0xRRGGBB`, the top byte is ignored.
+    pub const fn from_hex( hex: u32 ) -> Self {
+        Rgb::from_rgb((hex >> 16) as u8, (hex >> 8) as u8, hex as u8)
+    }
+
+    /// A packed 5-6-5 color as sent to RGB565 panels.
+    pub const fn from_rgb565( rgb565: u16 ) -> Self {
+        let (r, g, b) = ((rgb565 >> 11) as u8, (rgb565 >> 5) as u8 & 0x3F, rgb565 as u8 & 0x1F);
+
+        // Repeat the top bits so full scale stays full scale.
+        Rgb::from_rgb((r << 3) | (r >> 2), (g << 2) | (g >> 4), (b << 3) | (b >> 2))
+    }
+
+    /// `hue` in degrees, `saturation` and `value` from 0 to 255.
+    pub const fn from_hsv( hue: u16, saturation: u8, value: u8 ) -> Self {
+        let hue = hue % 360;
+        let (s, v) = (saturation as u32, value as u32);
+
+        // How far through the current sixth of the wheel, 0 to 255.
+        let f = (hue % 60) as u32 * 255 / 60;
+        let p = (v * (255 - s) / 255) as u8;
+        let q = (v * (255 - s * f / 255) / 255) as u8;
+        let t = (v * (255 - s * (255 - f) / 255) / 255) as u8;
+        let v = value;
+
+        match hue / 60 {
+            0 => Rgb::from_rgb(v, t, p),
+            1 => Rgb::from_rgb(q, v, p),
+            2 => Rgb::from_rgb(p, v, t),
+            3 => Rgb::from_rgb(p, q, v),
+            4 => Rgb::from_rgb(t, p, v),
+            _ => Rgb::from_rgb(v, p, q),
+        }
+    }
+
+    pub const fn r(&self) -> u8 {
+        self.r
+    }
+
+    pub const fn g(&self) -> u8 {
+        self.g
+    }
+
+    pub const fn b(&self) -> u8 {
+        self.b
+    }
+
+    /// Perceived brightness from 0 to 255.
+    pub fn luminance(&self) -> u8 {
+        mono::luma(*self)
+    }
+}
+
+/// On for every color but black, as `MonoStrategy::default()`.
+impl From<Rgb> for BinaryColor {
+    fn from(value: Rgb) -> Self {
+        if mono::luma(value) >= 1 {
+            BinaryColor::On
+        } else {
+            BinaryColor::Off
+        }
+    }
+}
+
+impl From<BinaryColor> for Rgb {
+    fn from(value: BinaryColor) -> Self {
+        match value {
+            BinaryColor::On => Rgb { r: 255, g: 255, b: 255 },
+            BinaryColor::Off => Rgb { r: 0, g: 0, b: 0 },
+        }
+    }
+}
+
+impl From<Rgb> for Gray2 {
+    fn from(value: Rgb) -> Self {
+        Gray2::new(mono::luma(value) >> 6)
+    }
+}
+
+impl From<Gray2> for Rgb {
+    fn from(value: Gray2) -> Self {
+        let luma = value.luma() * 0x55;
+        Rgb { r: luma, g: luma, b: luma }
+    }
+}
+
+impl From<Rgb> for Gray4 {
+    fn from(value: Rgb) -> Self {
+        Gray4::new(mono::luma(value) >> 4)
+    }
+}
+
+impl From<Gray4> for Rgb {
+    fn from(value: Gray4) -> Self {
+        let luma = value.luma() * 0x11;
+        Rgb { r: luma, g: luma, b: luma }
+    }
+}
+
+impl From<Rgb> for Gray8 {
+    fn from(value: Rgb) -> Self {
+        Gray8::new(mono::luma(value))
+    }
+}
+
+impl From<Gray8> for Rgb {
+    fn from(value: Gray8) -> Self {
+        let luma = value.luma();
+        Rgb { r: luma, g: luma, b: luma }
+    }
+}
+
+impl From<Rgb> for Rgb565 {
+    fn from(value: Rgb) -> Self {
+        Rgb565::new(value.r >> 3, value.g >> 2, value.b >> 3)
+    }
+}
+
+impl From<Rgb565> for Rgb {
+    fn from(value: Rgb565) -> Self {
+        Rgb::from_rgb565(value.into_storage())
+    }
+}
+
+impl From<Rgb> for Rgb888 {
+    fn from(value: Rgb) -> Self {
+        Rgb888::new(value.r, value.g, value.b)
+    }
+}
+
+impl From<Rgb888> for Rgb {
+    fn from(value: Rgb888) -> Self {
+        Rgb { r: value.r(), g: value.g(), b: value.b() }
+    }
+}
@@ -1,31 +1,17 @@
 
 use embedded_graphics::{
-    pixelcolor::{PixelColor, Rgb888, BinaryColor},
+    pixelcolor::PixelColor,
     prelude::*,
     primitives::{Rectangle, PrimitiveStyleBuilder},
+    mono_font::MonoTextStyle,
+    text::Baseline,
 
 };
 
-use core::cmp::{min, max};
-
 use super::*;
-
-impl From<Rgb> for Rgb888 {
-    fn from(value: Rgb) -> Self {
-        Rgb888::new(value.r, value.g, value.b)
-    }
-}
-
-impl From<Rgb> for BinaryColor {
-    fn from(value: Rgb) -> Self {
-
-        if value.r.saturating_add(value.b).saturating_add(value.g) > 0 {
-            BinaryColor::On
-        } else {
-            BinaryColor::Off
-        }
-    }
-}
+use embedded_graphics::prelude::Point;
+use mono::Quantizer;
+use blend::mono_color;
 
 pub struct EmbeddedRender<'a, D: DrawTarget<Color = C>, C: PixelColor> {
     width: u32,
@@ -34,12 +20,13 @@ pub struct EmbeddedRender<'a, D: DrawTarget<Color = C>, C: PixelColor> {
     chunk_width: u32,
     chunk_height: u32,
     clip: BoundingBox,
+    mono: Option<MonoStrategy>,
 }
 
 impl<'a, D: DrawTarget<Color = C>, C: PixelColor> EmbeddedRender<'a, D, C> {
     pub fn new(display: &'a mut D, chunk_width: u32, chunk_height: u32) -> Self {
         let bounds = display.bounding_box();
-        let clip = BoundingBox::new(0, 0, chunk_width, chunk_height);
+        let clip = BoundingBox::new(0, 0, chunk_width as i32, chunk_height as i32);
         EmbeddedRender {
             width: bounds.size.width , //BUG: why is this safe?
             height: bounds.size.height, //BUG: why is this safe?,
@@ -47,9 +34,18 @@ impl<'a, D: DrawTarget<Color = C>, C: PixelColor> EmbeddedRender<'a, D, C> {
             chunk_width,
             chunk_height,
             clip,
+            mono: None,
         }
     }
 
+    /// Quantize colors to black and white with `mono` before they are
+    /// handed to the display. Use this for one bit targets, where the
+    /// `From<Rgb>` conversion can only threshold. `None`, the default,
+    /// passes colors through.
+    pub fn set_mono_strategy(&mut self, mono: Option<MonoStrategy>) {
+        self.mono = mono;
+    }
+
     pub fn get_display(&self) -> &D {
         &self.display
     }
@@ -59,8 +55,10 @@ impl<'a, D: DrawTarget<Color = C>, C: PixelColor> EmbeddedRender<'a, D, C> {
     }
 }
 
-impl<'a, D: DrawTarget<Color = C>, C: PixelColor> Renderer for EmbeddedRender<'a, D, C> 
-    where C: From<Rgb> {
+/// Commands can be in any color that converts to the display's, which
+/// costs nothing when they are the same.
+impl<'a, D: DrawTarget<Color = T>, T: PixelColor, C: Color> Renderer<C> for EmbeddedRender<'a, D, T> 
+    where T: From<C> {
     fn width(&self) -> u32 {
         self.width
     }
@@ -79,58 +77,182 @@ impl<'a, D: DrawTarget<Color = C>, C: PixelColor> Renderer for EmbeddedRender<'a
             return Err(RendererError::InvalidChunkOffset{x, y})
         }
 
-        self.clip = BoundingBox{
-            x1: x,
-            y1: y,
-            x2: x + self.chunk_width,
-            y2: y + self.chunk_height,
-        };
+        let (x, y) = (x as i32, y as i32);
+        self.clip = BoundingBox::new(x, y, x + self.chunk_width as i32, y + self.chunk_height as i32)
+            .intersection(&BoundingBox::new(0, 0, self.width as i32, self.height as i32));
 
         Ok(())
     }
 
-    fn draw(&mut self, command: &Command) -> Result<(), RendererError> {
+    fn draw(&mut self, command: &Command<C>) -> Result<(), RendererError> {
         use CommandType::*;
 
         let clip = self.clip;
-        match command.flavor {
-            Null => Ok(()),
-            Rect(rgb) => {
+        let mut quantizer = self.mono.map(|mono| Quantizer::new(mono, &clip));
 
+        // The single color of a paint once quantized, if it has one.
+        let uniform = |quantizer: &Option<Quantizer>, paint: &Paint<C>| match (quantizer, paint) {
+            (None, Paint::Solid(color)) => Some(*color),
+            (None, _) => None,
+            (Some(quantizer), paint) => quantizer.uniform(paint).map(mono_color),
+        };
 
-                let x1 = max(command.bounds.x1, clip.x1);
-                let y1 = max(command.bounds.y1, clip.y1);
-                let x2 = min(command.bounds.x2, clip.x2);
-                let y2 = min(command.bounds.y2, clip.y2);
+        match command.flavor {
+            Null => Ok(()),
+            Rect(paint) if uniform(&quantizer, &paint).is_some() => {
+                let color = uniform(&quantizer, &paint).unwrap_or_else(|| mono_color(false));
 
-                let width = (x2 - x1) as u32; // BUG is this safe
-                let height = (y2 - y1) as u32; // Bug is this safe
-                let x = x1 as i32; // Bug is this safe
-                let y = y1 as i32; // Bug is this safe
+                let area = command.bounds.intersection(&clip);
 
-                let color = rgb.into();
+                let fill = T::from(color);
 
                 let line_style = PrimitiveStyleBuilder::new()
-                    .fill_color(color)
+                    .fill_color(fill)
                     .build();
 
-                Rectangle::new(Point::new(x, y), Size::new(width, height))
+                Rectangle::new(Point::new(area.x1, area.y1), Size::new(area.width(), area.height()))
                     .into_styled(line_style)
                     .draw(self.display)
                     .map_err(|_e| RendererError::BackingError)?;
                 
                 Ok(())
             }
+            Rect(_) | StyledRect { .. } | Circle(_) | Ellipse(_) | Line { .. } | Polyline { .. }
+            | QuadraticBezier { .. } | CubicBezier { .. } | Triangle { .. } | Polygon { .. }
+            | Arc { .. } | Sector { .. } => {
+                let display = &mut *self.display;
+                let mut result = Ok(());
+
+                command.spans(&clip, |span, paint| {
+                    if result.is_err() {
+                        return;
+                    }
+
+                    result = match uniform(&quantizer, &paint) {
+                        Some(color) => {
+                            let area = Rectangle::new(
+                                Point::new(span.x1, span.y),
+                                Size::new(span.len(), 1),
+                            );
+                            display.fill_solid(&area, T::from(color))
+                        }
+                        None => {
+                            let quantizer = &mut quantizer;
+                            let pixels = (span.x1..span.x2).map(|x| {
+                                let point = Point::new(x, span.y);
+                                let color = match quantizer {
+                                    Some(quantizer) => mono_color(quantizer.on(&paint, x, span.y)),
+                                    None => paint.color_at(x, span.y),
+                                };
+                                Pixel(point, T::from(color))
+                            });
+                            display.draw_iter(pixels)
+                        }
+                    }
+                    .map_err(|_e| RendererError::BackingError);
+                });
+
+                result
+            }
+            Text { text, font, color } => {
+                // Dithered text is unreadable so it is either on or off.
+                let color = match &quantizer {
+                    Some(quantizer) => mono_color(quantizer.solid_on(color)),
+                    None => color,
+                };
+
+                // Nothing may land outside the measured bounds, even if
+                // the font draws a glyph larger than its cell.
+                let area = command.bounds.intersection(&clip);
+                let area = Rectangle::new(
+                    Point::new(area.x1, area.y1),
+                    Size::new(area.width(), area.height()),
+                );
+                let style = MonoTextStyle::new(font.0, T::from(color));
+                let position = Point::new(command.bounds.x1, command.bounds.y1);
+
+                embedded_graphics::text::Text::with_baseline(text, position, style, Baseline::Top)
+                    .draw(&mut self.display.clipped(&area))
+                    .map_err(|_e| RendererError::BackingError)?;
+
+                Ok(())
+            }
+            Image(image) => {
+                let bounds = command.bounds;
+                let area = bounds.intersection(&clip);
+
+                let pixels = (area.y1..area.y2)
+                    .flat_map(|y| (area.x1..area.x2).map(move |x| (x, y)))
+                    .filter_map(|(x, y)| {
+                        let color = C::from(image.pixel((x - bounds.x1) as u32, (y - bounds.y1) as u32)?);
+                        let color = match &mut quantizer {
+                            Some(quantizer) => mono_color(quantizer.on(&Paint::Solid(color), x, y)),
+                            None => color,
+                        };
+                        Some(Pixel(Point::new(x, y), T::from(color)))
+                    });
+
+                self.display.draw_iter(pixels)
+                    .map_err(|_e| RendererError::BackingError)?;
+
+                Ok(())
+            }
         }
     }
 
+    fn draw_blended(&mut self, command: &Command<C>, below: Below<'_, C>) -> Result<(), RendererError> {
+        let clip = self.clip;
+        let mono = self.mono;
+        let mut quantizer = mono.map(|mono| Quantizer::new(mono, &clip));
+        let display = &mut *self.display;
+        let mut result = Ok(());
+
+        let (blend, opacity) = (command.blend, command.opacity);
+
+        // One bit targets without a strategy flip pixels too, after the
+        // same conversion every pixel goes through.
+        let one_bit = T::Raw::BITS_PER_PIXEL == 1;
+        let on = |color: C| T::from(color) == T::from(mono_color::<C>(true));
+        let mix = |blend: BlendMode, opacity, src, dst| match mono {
+            Some(mono) => blend.blend_bits(opacity, src, dst, |color| mono.solid_on(color)),
+            None if one_bit => blend.blend_bits(opacity, src, dst, on),
+            None => blend.blend(opacity, src, dst),
+        };
+
+        blend::composite(command, below, &clip, mix, |x, y, src, dst| {
+            let color = match (&mut quantizer, blend, opacity) {
+                // Flip what was drawn beneath rather than inverting
+                // its color, as a one bit panel would.
+                (Some(quantizer), BlendMode::Invert, 255) => {
+                    mono_color(!quantizer.on(&Paint::Solid(dst), x, y))
+                }
+                (Some(quantizer), BlendMode::Xor, 255) => {
+                    let flip = quantizer.solid_on(src);
+                    mono_color(quantizer.on(&Paint::Solid(dst), x, y) != flip)
+                }
+                (Some(quantizer), blend, opacity) => {
+                    mono_color(quantizer.on(&Paint::Solid(blend.blend(opacity, src, dst)), x, y))
+                }
+                (None, blend, opacity) => mix(blend, opacity, src, dst),
+            };
+            let pixel = Pixel(Point::new(x, y), T::from(color));
+
+            if result.is_ok() {
+                result = display.draw_iter(core::iter::once(pixel))
+                    .map_err(|_e| RendererError::BackingError);
+            }
+        });
+
+        result
+    }
+
     fn clear(&mut self) -> Result<(), RendererError> {
         let clip = self.clip;
-        let top_left = Point::new(clip.x1 as i32, clip.y1 as i32);
-        let size = Size::new((clip.x2 - clip.x1) as u32, (clip.y2 - clip.y1) as u32);
+        let top_left = Point::new(clip.x1, clip.y1);
+        let size = Size::new(clip.width(), clip.height());
         let area = Rectangle::new(top_left, size);
         let mut clipped = self.display.clipped(&area);
-        let clear_color = Rgb::new(0, 0, 0).into();
+        let clear_color = T::from(C::from(Rgb { r: 0, g: 0, b: 0 }));
         clipped.clear(clear_color)
         .map_err(|_e| RendererError::BackingError)?;
 
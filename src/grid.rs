@@ -0,0 +1,199 @@
+use core::cmp::min;
+
+use super::*;
+
+/// How the screen is cut into tiles. Bins are boxes of tile columns
+/// and rows, the same as a `BoundingBox` is of pixels.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) struct Grid {
+    width: u32,
+    height: u32,
+    step: (u32, u32),
+}
+
+impl Grid {
+    pub(crate) fn new(width: u32, height: u32, step: (u32, u32)) -> Self {
+        Self { width, height, step }
+    }
+
+    pub(crate) fn columns(&self) -> u32 {
+        self.width.div_ceil(self.step.0)
+    }
+
+    pub(crate) fn rows(&self) -> u32 {
+        self.height.div_ceil(self.step.1)
+    }
+
+    pub(crate) fn tiles(&self) -> usize {
+        self.columns() as usize * self.rows() as usize
+    }
+
+    /// The column and row of the `index`th tile, counting down each
+    /// column and then across.
+    pub(crate) fn tile_at(&self, index: usize) -> Point {
+        let rows = self.rows() as usize;
+        Point::new((index / rows) as i32, (index % rows) as i32)
+    }
+
+    /// The pixels of the tile at column and row `at`. Tiles on the
+    /// right and bottom edges stop at the edge of the screen.
+    pub(crate) fn bounds(&self, at: Point) -> BoundingBox {
+        let (width, height) = (self.step.0 as i32, self.step.1 as i32);
+        let (x1, y1) = (at.x * width, at.y * height);
+        BoundingBox::new(x1, y1, x1 + width, y1 + height).intersection(&self.screen())
+    }
+
+    /// The pixels of the screen.
+    pub(crate) fn screen(&self) -> BoundingBox {
+        BoundingBox::new(0, 0, self.width as i32, self.height as i32)
+    }
+
+    /// The tiles `command` might draw in, which is none if it is
+    /// entirely off the screen.
+    pub(crate) fn bin<C>(&self, command: &Command<C>) -> BoundingBox {
+        let bounds = command.bounds.intersection(&self.screen());
+        if !command.visible || matches!(command.flavor, CommandType::Null) || bounds.is_empty() {
+            return BoundingBox::new(0, 0, 0, 0);
+        }
+
+        // On screen, so nothing is negative.
+        BoundingBox::new(
+            bounds.x1 / self.step.0 as i32,
+            bounds.y1 / self.step.1 as i32,
+            (bounds.x2 as u32).div_ceil(self.step.0) as i32,
+            (bounds.y2 as u32).div_ceil(self.step.1) as i32,
+        )
+    }
+}
+
+/// How many words a `TileMap` needs for a `width` by `height` screen
+/// drawn in `chunk_width` by `chunk_height` tiles.
+pub const fn tile_words(width: u32, height: u32, chunk_width: u32, chunk_height: u32) -> usize {
+    let tiles = width.div_ceil(chunk_width) as usize * height.div_ceil(chunk_height) as usize;
+    tiles.div_ceil(u32::BITS as usize)
+}
+
+/// A bit per tile, in the order tiles are drawn.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) struct TileMap<const WORDS: usize> {
+    bits: [u32; WORDS],
+    /// Every tile is marked, which is all that can be said when the
+    /// grid has more tiles than there are bits.
+    all: bool,
+}
+
+impl<const WORDS: usize> TileMap<WORDS> {
+    pub(crate) const fn new() -> Self {
+        Self { bits: [0; WORDS], all: false }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        !self.all && self.bits.iter().all(|&word| word == 0)
+    }
+
+    pub(crate) fn mark_all(&mut self) {
+        self.all = true;
+    }
+
+    /// Mark every tile in `bin`.
+    pub(crate) fn mark(&mut self, grid: &Grid, bin: &BoundingBox) {
+        self.mark_before(grid, bin, usize::MAX);
+    }
+
+    /// Mark the tiles in `bin` that come before the `end`th.
+    pub(crate) fn mark_before(&mut self, grid: &Grid, bin: &BoundingBox, end: usize) {
+        if grid.tiles() > WORDS * u32::BITS as usize {
+            self.all = true;
+            return;
+        }
+
+        let rows = grid.rows() as usize;
+        for column in bin.x1..bin.x2 {
+            for row in bin.y1..bin.y2 {
+                let index = column as usize * rows + row as usize;
+                if index < end {
+                    self.bits[index / u32::BITS as usize] |= 1 << (index % u32::BITS as usize);
+                }
+            }
+        }
+    }
+
+    pub(crate) fn contains(&self, index: usize) -> bool {
+        let bits = u32::BITS as usize;
+        self.all || self.bits.get(index / bits).is_some_and(|word| word & (1 << (index % bits)) != 0)
+    }
+
+    /// The first marked tile from `index` on, or `grid.tiles()` if
+    /// there isn't one.
+    pub(crate) fn next(&self, grid: &Grid, index: usize) -> usize {
+        let tiles = grid.tiles();
+        if self.all || index >= tiles {
+            return min(index, tiles);
+        }
+
+        let bits = u32::BITS as usize;
+        let mut word = index / bits;
+        if word >= WORDS {
+            return tiles;
+        }
+        let mut marked = self.bits[word] & (u32::MAX << (index % bits));
+
+        while marked == 0 {
+            word += 1;
+            if word >= WORDS {
+                return tiles;
+            }
+            marked = self.bits[word];
+        }
+
+        min(tiles, word * bits + marked.trailing_zeros() as usize)
+    }
+}
+
+/// Which tiles the command in each slot might draw in, kept by words
+/// of 32 tiles so finding the commands in a tile is a bit test per
+/// slot along one array. With more tiles than `WORDS` words hold it
+/// keeps nothing and can't say.
+pub(crate) struct TileIndex<const SLOTS: usize, const WORDS: usize> {
+    bits: [[u32; SLOTS]; WORDS],
+}
+
+impl<const SLOTS: usize, const WORDS: usize> TileIndex<SLOTS, WORDS> {
+    pub(crate) const fn new() -> Self {
+        Self { bits: [[0; SLOTS]; WORDS] }
+    }
+
+    /// Move `slot` from the tiles in `old` to the tiles in `new`.
+    pub(crate) fn rebin(&mut self, grid: &Grid, slot: usize, old: &BoundingBox, new: &BoundingBox) {
+        if grid.tiles() > WORDS * u32::BITS as usize {
+            return;
+        }
+
+        let rows = grid.rows() as usize;
+        for (bin, set) in [(old, false), (new, true)] {
+            for column in bin.x1..bin.x2 {
+                for row in bin.y1..bin.y2 {
+                    let index = column as usize * rows + row as usize;
+                    let word = &mut self.bits[index / u32::BITS as usize][slot];
+                    let bit = 1 << (index % u32::BITS as usize);
+                    if set {
+                        *word |= bit;
+                    } else {
+                        *word &= !bit;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Might `slot` draw in the `index`th tile? `None` if there are too
+    /// many tiles to know.
+    pub(crate) fn contains(&self, grid: &Grid, index: usize, slot: usize) -> Option<bool> {
+        let bits = u32::BITS as usize;
+        if grid.tiles() > WORDS * bits {
+            return None;
+        }
+
+        Some(self.bits[index / bits][slot] & (1 << (index % bits)) != 0)
+    }
+}
@@ -0,0 +1,163 @@
+use super::*;
+
+/// How the pixels of an `ImageData` are stored.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ImageFormat {
+    /// One bit per pixel packed in columns like the SH1107 page
+    /// buffer: each byte is 8 pixels going down with the least
+    /// significant bit on top, and each run of `width` bytes is the
+    /// next 8 rows. Set bits are white.
+    Mono,
+    /// One byte of gray per pixel, row major.
+    Gray8,
+    /// Two big endian bytes per pixel, row major.
+    Rgb565,
+}
+
+impl ImageFormat {
+    const fn data_len(&self, width: u32, height: u32) -> usize {
+        let (width, height) = (width as usize, height as usize);
+        match self {
+            ImageFormat::Mono => width * height.div_ceil(8),
+            ImageFormat::Gray8 => width * height,
+            ImageFormat::Rgb565 => 2 * width * height,
+        }
+    }
+}
+
+/// Which pixels of an image are left undrawn.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Transparency {
+    Opaque,
+    /// Pixels of exactly this color are skipped.
+    Key(Rgb),
+    /// A `ImageFormat::Mono` packed mask the same size as the image.
+    /// Pixels whose mask bit is clear are skipped.
+    Mask(&'static [u8]),
+}
+
+/// Static pixel data for an image command. Commands borrow it, so
+/// it is usually a `const` or `static` itself, which the constructors
+/// allow.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ImageData {
+    data: &'static [u8],
+    format: ImageFormat,
+    width: u32,
+    height: u32,
+    transparency: Transparency,
+}
+
+impl ImageData {
+    pub const fn new(
+        data: &'static [u8],
+        format: ImageFormat,
+        width: u32,
+        height: u32,
+    ) -> Result<Self, CommandError> {
+        if data.len() < format.data_len(width, height) {
+            return Err(CommandError::ImageDataTooShort);
+        }
+
+        Ok(ImageData {
+            data,
+            format,
+            width,
+            height,
+            transparency: Transparency::Opaque,
+        })
+    }
+
+    /// Skip pixels of the `key` color.
+    pub const fn with_key(mut self, key: Rgb) -> Self {
+        self.transparency = Transparency::Key(key);
+        self
+    }
+
+    /// Skip pixels whose bit in the column packed `mask` is clear.
+    pub const fn with_mask(mut self, mask: &'static [u8]) -> Result<Self, CommandError> {
+        if mask.len() < ImageFormat::Mono.data_len(self.width, self.height) {
+            return Err(CommandError::ImageDataTooShort);
+        }
+
+        self.transparency = Transparency::Mask(mask);
+        Ok(self)
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn format(&self) -> ImageFormat {
+        self.format
+    }
+
+    pub fn transparency(&self) -> Transparency {
+        self.transparency
+    }
+
+    pub(crate) fn data(&self) -> &'static [u8] {
+        self.data
+    }
+
+    pub(crate) fn is_opaque(&self) -> bool {
+        self.transparency == Transparency::Opaque
+    }
+
+    /// The color of the pixel at `x`, `y` in image coordinates or
+    /// `None` if it is transparent.
+    pub(crate) fn pixel(&self, x: u32, y: u32) -> Option<Rgb> {
+        let index = (y * self.width + x) as usize;
+
+        let rgb = match self.format {
+            ImageFormat::Mono => {
+                if mono_bit(self.data, self.width, x, y) {
+                    Rgb { r: 255, g: 255, b: 255 }
+                } else {
+                    Rgb { r: 0, g: 0, b: 0 }
+                }
+            }
+            ImageFormat::Gray8 => {
+                let gray = self.data[index];
+                Rgb { r: gray, g: gray, b: gray }
+            }
+            ImageFormat::Rgb565 => {
+                Rgb::from_rgb565(u16::from_be_bytes([self.data[2 * index], self.data[2 * index + 1]]))
+            }
+        };
+
+        match self.transparency {
+            Transparency::Opaque => Some(rgb),
+            Transparency::Key(key) => (rgb != key).then_some(rgb),
+            Transparency::Mask(mask) => mono_bit(mask, self.width, x, y).then_some(rgb),
+        }
+    }
+}
+
+fn mono_bit(data: &[u8], width: u32, x: u32, y: u32) -> bool {
+    let byte = data[((y / 8) * width + x) as usize];
+    byte & (1 << (y % 8)) != 0
+}
+
+/// The 8 bits of column `x` of column packed `data` starting at row
+/// `y`, which may be above the image. Rows outside the image are 0.
+pub(crate) fn mono_column(data: &[u8], width: u32, height: u32, x: u32, y: i64) -> u8 {
+    let pages = height.div_ceil(8) as i64;
+    let page = y.div_euclid(8);
+    let shift = y.rem_euclid(8);
+
+    let byte = |page: i64| -> u16 {
+        if page < 0 || page >= pages {
+            0
+        } else {
+            data[(page as u32 * width + x) as usize] as u16
+        }
+    };
+
+    let window = byte(page) | (byte(page + 1) << 8);
+    (window >> shift) as u8
+}
@@ -1,91 +1,839 @@
 #![no_std]
 
 use core::mem;
-use core::cmp::min;
+use core::cmp::{min, max};
 use core::result::{Result, Result::{Ok, Err}};
 use core::convert::From;
 use core::iter::Iterator;
 
+use grid::{Grid, TileIndex, TileMap};
+use occlusion::Coverage;
+
+use embedded_graphics::mono_font::MonoFont;
+
 pub mod embedded_render;
 pub mod sh1107_render;
+mod raster;
+mod text;
+mod image;
+mod paint;
+mod mono;
+mod blend;
+mod color;
+mod palette;
+mod grid;
+mod occlusion;
+
+pub use paint::Paint;
+pub use mono::{MonoStrategy, MAX_DIFFUSION_WIDTH};
+pub use blend::{BlendMode, Below, Rgba};
+pub use color::Color;
+pub use palette::{Indexed, Palette, PALETTE_SIZE};
+pub use grid::tile_words;
+pub use text::Font;
+pub use image::{ImageData, ImageFormat, Transparency};
 
 #[derive(Debug)]
 pub enum RenderError {
     BackingError,
 }
 
+/// The pixels `x1..x2` by `y1..y2`. `x2` and `y2` are one past the
+/// last column and row, so a box with `x1 == x2` holds nothing. Boxes
+/// can reach past any edge of the screen, only the part on it is drawn.
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub struct BoundingBox {
-    pub x1: u32,
-    pub y1: u32,
-    pub x2: u32,
-    pub y2: u32,
+    pub x1: i32,
+    pub y1: i32,
+    pub x2: i32,
+    pub y2: i32,
 } 
 
 impl BoundingBox {
-    pub fn new( x1: u32, y1: u32, x2: u32, y2: u32) -> Self {
+    pub fn new( x1: i32, y1: i32, x2: i32, y2: i32) -> Self {
         Self { x1, y1, x2, y2 }
     }
+
+    pub fn width(&self) -> u32 {
+        if self.x2 > self.x1 { self.x2.abs_diff(self.x1) } else { 0 }
+    }
+
+    pub fn height(&self) -> u32 {
+        if self.y2 > self.y1 { self.y2.abs_diff(self.y1) } else { 0 }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width() == 0 || self.height() == 0
+    }
+
+    pub fn contains(&self, point: Point) -> bool {
+        (self.x1..self.x2).contains(&point.x) && (self.y1..self.y2).contains(&point.y)
+    }
+
+    /// The pixels in both, which is empty if they don't overlap.
+    pub fn intersection(&self, other: &Self) -> Self {
+        let x1 = max(self.x1, other.x1);
+        let y1 = max(self.y1, other.y1);
+
+        Self {
+            x1,
+            y1,
+            x2: max(x1, min(self.x2, other.x2)),
+            y2: max(y1, min(self.y2, other.y2)),
+        }
+    }
+
+    /// The smallest box holding both. An empty box adds nothing.
+    pub fn union(&self, other: &Self) -> Self {
+        match (self.is_empty(), other.is_empty()) {
+            (_, true) => *self,
+            (true, _) => *other,
+            _ => Self {
+                x1: min(self.x1, other.x1),
+                y1: min(self.y1, other.y1),
+                x2: max(self.x2, other.x2),
+                y2: max(self.y2, other.y2),
+            },
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Point {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl Point {
+    pub const fn new( x: i32, y: i32 ) -> Self {
+        Self { x, y }
+    }
+}
+
+/// A point with `i16` coordinates, for geometry every command carries
+/// where the bytes add up, such as gradient ends.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ShortPoint {
+    pub x: i16,
+    pub y: i16,
+}
+
+impl ShortPoint {
+    pub const fn new( x: i16, y: i16 ) -> Self {
+        Self { x, y }
+    }
+}
+
+impl From<ShortPoint> for Point {
+    fn from(value: ShortPoint) -> Self {
+        Point::new(value.x as i32, value.y as i32)
+    }
+}
+
+/// The most points a `Points` list can hold.
+pub const MAX_POINTS: usize = 8;
+
+/// A fixed capacity list of points so commands stay `Copy`, don't need
+/// an allocator and can be built from points computed at runtime.
+/// Coordinates are kept as `i16` to keep every command small.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Points {
+    xs: [i16; MAX_POINTS],
+    ys: [i16; MAX_POINTS],
+    len: u8,
+}
+
+impl Points {
+    pub fn new( points: &[Point] ) -> Result<Self, CommandError> {
+        if points.len() > MAX_POINTS {
+            return Err(CommandError::TooManyPoints);
+        }
+
+        let mut list = Points {
+            xs: [0; MAX_POINTS],
+            ys: [0; MAX_POINTS],
+            len: points.len() as u8,
+        };
+        for (i, point) in points.iter().enumerate() {
+            list.xs[i] = i16::try_from(point.x).map_err(|_| CommandError::PointOutOfRange)?;
+            list.ys[i] = i16::try_from(point.y).map_err(|_| CommandError::PointOutOfRange)?;
+        }
+
+        Ok(list)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, index: usize) -> Option<Point> {
+        (index < self.len()).then(|| Point::new(self.xs[index] as i32, self.ys[index] as i32))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Point> + '_ {
+        (0..self.len()).map(|i| Point::new(self.xs[i] as i32, self.ys[i] as i32))
+    }
+
+    /// The points widened back to `Point`s, and how many of them are
+    /// used, for the rasterizer which works on slices.
+    pub(crate) fn unpack(&self) -> ([Point; MAX_POINTS], usize) {
+        let mut points = [Point::new(0, 0); MAX_POINTS];
+        for (slot, point) in points.iter_mut().zip(self.iter()) {
+            *slot = point;
+        }
+
+        (points, self.len())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CommandError {
+    TooManyPoints,
+    /// `Points` store coordinates as `i16`.
+    PointOutOfRange,
+    ImageDataTooShort,
+    /// Text is a single line, so it can't hold line breaks or other
+    /// control characters.
+    ControlCharacter,
+}
+
+/// How a shape is painted. The stroke is drawn inside the shape's
+/// bounds and the fill covers whatever the stroke leaves.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ShapeStyle<C = Rgb> {
+    pub fill: Option<Paint<C>>,
+    pub stroke: Option<C>,
+    pub stroke_width: u32,
+}
+
+impl<C: Color> ShapeStyle<C> {
+    pub fn fill( paint: impl Into<Paint<C>> ) -> Self {
+        Self { fill: Some(paint.into()), stroke: None, stroke_width: 0 }
+    }
+
+    pub fn stroke( color: C, stroke_width: u32 ) -> Self {
+        Self { fill: None, stroke: Some(color), stroke_width }
+    }
+
+    pub fn fill_and_stroke( fill: impl Into<Paint<C>>, stroke: C, stroke_width: u32 ) -> Self {
+        Self { fill: Some(fill.into()), stroke: Some(stroke), stroke_width }
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
-pub struct Command {
-    epoch: u8,
+pub struct Command<C = Rgb> {
     bounds: BoundingBox,
-    flavor: CommandType,
+    flavor: CommandType<C>,
+    blend: BlendMode,
+    opacity: u8,
+    visible: bool,
 } 
 
-impl Command {
+impl<C: Color> Command<C> {
     const fn null() -> Self {
         Command {
-            epoch: 0,
             bounds: BoundingBox {
                 x1: 0,
                 y1: 0,
                 x2: 0,
                 y2: 0
             }, 
-            flavor: CommandType::Null
+            flavor: CommandType::Null,
+            blend: BlendMode::SourceOver,
+            opacity: 255,
+            visible: true,
         }
     }
 
-    pub fn new_rect( bounds: BoundingBox, rgb: Rgb ) -> Self {
+    pub fn new_rect( bounds: BoundingBox, paint: impl Into<Paint<C>> ) -> Self {
         Command {
-            epoch: 0,
             bounds,
-            flavor: CommandType::Rect(rgb),
+            flavor: CommandType::Rect(paint.into()),
+            blend: BlendMode::SourceOver,
+            opacity: 255,
+            visible: true,
+        }
+    }
+
+    /// A rectangle with an optional fill, a stroke drawn inside
+    /// `bounds` and corners rounded by `corner_radius`.
+    pub fn new_styled_rect( bounds: BoundingBox, style: ShapeStyle<C>, corner_radius: u32 ) -> Self {
+        Command {
+            bounds,
+            flavor: CommandType::StyledRect { style, corner_radius },
+            blend: BlendMode::SourceOver,
+            opacity: 255,
+            visible: true,
+        }
+    }
+
+    /// A circle with the given diameter whose bounding box starts
+    /// at `top_left`.
+    pub fn new_circle( top_left: Point, diameter: u32, style: ShapeStyle<C> ) -> Self {
+        let bounds = BoundingBox::new(
+            top_left.x,
+            top_left.y,
+            top_left.x + diameter as i32,
+            top_left.y + diameter as i32,
+        );
+
+        Command {
+            bounds,
+            flavor: CommandType::Circle(style),
+            blend: BlendMode::SourceOver,
+            opacity: 255,
+            visible: true,
+        }
+    }
+
+    /// An ellipse inscribed in `bounds`.
+    pub fn new_ellipse( bounds: BoundingBox, style: ShapeStyle<C> ) -> Self {
+        Command {
+            bounds,
+            flavor: CommandType::Ellipse(style),
+            blend: BlendMode::SourceOver,
+            opacity: 255,
+            visible: true,
+        }
+    }
+
+    /// A round capped line from `start` to `end`.
+    pub fn new_line( start: Point, end: Point, stroke_width: u32, color: C ) -> Self {
+        Command {
+            bounds: raster::stroke_bounds(&[start, end], stroke_width),
+            flavor: CommandType::Line { start, end, stroke_width, color },
+            blend: BlendMode::SourceOver,
+            opacity: 255,
+            visible: true,
+        }
+    }
+
+    /// Connected round capped lines through up to `MAX_POINTS` points.
+    pub fn new_polyline( points: &[Point], stroke_width: u32, color: C ) -> Result<Self, CommandError> {
+        Ok(Command {
+            bounds: raster::stroke_bounds(points, stroke_width),
+            flavor: CommandType::Polyline {
+                points: Points::new(points)?,
+                stroke_width,
+                color,
+            },
+            blend: BlendMode::SourceOver,
+            opacity: 255,
+            visible: true,
+        })
+    }
+
+    /// A quadratic Bézier curve from `start` to `end` bent towards
+    /// `control`.
+    pub fn new_quadratic_bezier(
+        start: Point,
+        control: Point,
+        end: Point,
+        stroke_width: u32,
+        color: C,
+    ) -> Self {
+        let control = [start, control, end];
+
+        Command {
+            bounds: raster::curve_bounds(&control, stroke_width),
+            flavor: CommandType::QuadraticBezier { control, stroke_width, color },
+            blend: BlendMode::SourceOver,
+            opacity: 255,
+            visible: true,
+        }
+    }
+
+    /// A cubic Bézier curve from `start` to `end` bent towards
+    /// `control1` and then `control2`.
+    pub fn new_cubic_bezier(
+        start: Point,
+        control1: Point,
+        control2: Point,
+        end: Point,
+        stroke_width: u32,
+        color: C,
+    ) -> Self {
+        let control = [start, control1, control2, end];
+
+        Command {
+            bounds: raster::curve_bounds(&control, stroke_width),
+            flavor: CommandType::CubicBezier { control, stroke_width, color },
+            blend: BlendMode::SourceOver,
+            opacity: 255,
+            visible: true,
+        }
+    }
+
+    /// A filled triangle with corners at `a`, `b` and `c`.
+    pub fn new_triangle( a: Point, b: Point, c: Point, paint: impl Into<Paint<C>> ) -> Self {
+        Command {
+            bounds: raster::polygon_bounds(&[a, b, c]),
+            flavor: CommandType::Triangle { vertices: [a, b, c], paint: paint.into() },
+            blend: BlendMode::SourceOver,
+            opacity: 255,
+            visible: true,
+        }
+    }
+
+    /// A filled polygon with up to `MAX_POINTS` corners. Concave
+    /// polygons are filled with the even-odd rule.
+    pub fn new_polygon( points: &[Point], paint: impl Into<Paint<C>> ) -> Result<Self, CommandError> {
+        Ok(Command {
+            bounds: raster::polygon_bounds(points),
+            flavor: CommandType::Polygon {
+                points: Points::new(points)?,
+                paint: paint.into(),
+            },
+            blend: BlendMode::SourceOver,
+            opacity: 255,
+            visible: true,
+        })
+    }
+
+    /// An arc of a circle of `radius` pixels around `center`, drawn
+    /// `stroke_width` pixels thick. Angles are in degrees, zero points
+    /// right and positive angles turn clockwise.
+    pub fn new_arc(
+        center: Point,
+        radius: u32,
+        start: i32,
+        sweep: i32,
+        stroke_width: u32,
+        color: C,
+    ) -> Self {
+        Command {
+            bounds: raster::arc_bounds(center, radius),
+            flavor: CommandType::Arc { center, radius, start, sweep, stroke_width, color },
+            blend: BlendMode::SourceOver,
+            opacity: 255,
+            visible: true,
+        }
+    }
+
+    /// A filled pie slice of a circle of `radius` pixels around
+    /// `center`. Angles work like `new_arc`.
+    pub fn new_sector(
+        center: Point,
+        radius: u32,
+        start: i32,
+        sweep: i32,
+        paint: impl Into<Paint<C>>,
+    ) -> Self {
+        Command {
+            bounds: raster::arc_bounds(center, radius),
+            flavor: CommandType::Sector { center, radius, start, sweep, paint: paint.into() },
+            blend: BlendMode::SourceOver,
+            opacity: 255,
+            visible: true,
+        }
+    }
+
+    /// `text` set in `font` with its top left corner at `top_left`.
+    /// The bounds come from the font metrics so they track the length
+    /// of the text. Like the font, the text is borrowed. Text is a
+    /// single line and control characters such as `'\n'` are an error.
+    pub fn new_text(
+        top_left: Point,
+        text: &'static str,
+        font: &'static MonoFont<'static>,
+        color: C,
+    ) -> Result<Self, CommandError> {
+        if text.chars().any(char::is_control) {
+            return Err(CommandError::ControlCharacter);
+        }
+
+        let font = Font(font);
+        let (width, height) = font.measure(text);
+        let bounds = BoundingBox::new(
+            top_left.x,
+            top_left.y,
+            top_left.x + width as i32,
+            top_left.y + height as i32,
+        );
+
+        Ok(Command {
+            bounds,
+            flavor: CommandType::Text {
+                text,
+                font,
+                color,
+            },
+            blend: BlendMode::SourceOver,
+            opacity: 255,
+            visible: true,
+        })
+    }
+
+    /// `image` with its top left corner at `top_left`. The image is
+    /// borrowed, like its pixels, so every command stays small.
+    pub fn new_image( top_left: Point, image: &'static ImageData ) -> Self {
+        let bounds = BoundingBox::new(
+            top_left.x,
+            top_left.y,
+            top_left.x + image.width() as i32,
+            top_left.y + image.height() as i32,
+        );
+
+        Command {
+            bounds,
+            flavor: CommandType::Image(image),
+            blend: BlendMode::SourceOver,
+            opacity: 255,
+            visible: true,
+        }
+    }
+
+    /// A rectangle of `rgba` blended over what is beneath it, like a
+    /// dimmed backdrop behind a dialog.
+    pub fn new_translucent_rect( bounds: BoundingBox, rgba: Rgba ) -> Self {
+        Self::new_rect(bounds, C::from(rgba.rgb())).with_opacity(rgba.a)
+    }
+
+    /// A rectangle that inverts whatever is beneath it.
+    pub fn new_inverted_rect( bounds: BoundingBox ) -> Self {
+        Self::new_rect(bounds, C::from(Rgb { r: 255, g: 255, b: 255 })).with_blend(BlendMode::Invert)
+    }
+
+    /// Combine this command with what is beneath it using `blend`.
+    pub fn with_blend( mut self, blend: BlendMode ) -> Self {
+        self.blend = blend;
+        self
+    }
+
+    /// Mix this command with what is beneath it, 255 being opaque.
+    pub fn with_opacity( mut self, opacity: u8 ) -> Self {
+        self.opacity = opacity;
+        self
+    }
+
+    /// Hide or show the command. Hidden commands keep their place in
+    /// a display list but aren't drawn.
+    pub fn with_visible( mut self, visible: bool ) -> Self {
+        self.visible = visible;
+        self
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    /// Does this command replace what is beneath it rather than
+    /// blending with it?
+    pub fn is_opaque(&self) -> bool {
+        self.blend == BlendMode::SourceOver && self.opacity == 255
+    }
+
+    /// Call `f` with the position and color of every pixel this command
+    /// paints inside `clip`, before blending.
+    pub(crate) fn pixels(&self, clip: &BoundingBox, mut f: impl FnMut(i32, i32, C)) {
+        use CommandType::*;
+
+        let bounds = self.bounds;
+        match &self.flavor {
+            Text { text, font, color } => {
+                let glyph_width = font.0.character_size.width;
+                let advance = glyph_width + font.0.character_spacing;
+
+                for (i, c) in text.chars().enumerate() {
+                    let glyph_x = bounds.x1 + (i as u32 * advance) as i32;
+                    let x1 = max(glyph_x, clip.x1);
+                    let x2 = min(glyph_x + glyph_width as i32, clip.x2);
+
+                    for y in max(bounds.y1, clip.y1)..min(bounds.y2, clip.y2) {
+                        for x in x1..x2 {
+                            if font.glyph_pixel(c, (x - glyph_x) as u32, (y - bounds.y1) as u32) {
+                                f(x, y, *color);
+                            }
+                        }
+                    }
+                }
+            }
+            Image(image) => {
+                for y in max(bounds.y1, clip.y1)..min(bounds.y2, clip.y2) {
+                    for x in max(bounds.x1, clip.x1)..min(bounds.x2, clip.x2) {
+                        if let Some(rgb) = image.pixel((x - bounds.x1) as u32, (y - bounds.y1) as u32) {
+                            f(x, y, C::from(rgb));
+                        }
+                    }
+                }
+            }
+            _ => self.spans(clip, |span, paint| {
+                for x in span.x1..span.x2 {
+                    f(x, span.y, paint.color_at(x, span.y));
+                }
+            }),
+        }
+    }
+
+    /// Call `f` with every run of pixels this command paints inside
+    /// `clip`. Only implemented for the shapes that go through the
+    /// shared rasterizer.
+    pub(crate) fn spans(&self, clip: &BoundingBox, mut f: impl FnMut(raster::Span, Paint<C>)) {
+        use CommandType::*;
+
+        match &self.flavor {
+            Null | Text { .. } | Image(_) => (),
+            Rect(paint) => {
+                for y in raster::rows(&self.bounds, clip) {
+                    raster::emit(clip, y, self.bounds.x1 as i64, self.bounds.x2 as i64, *paint, &mut f);
+                }
+            }
+            Circle(style) | Ellipse(style) => {
+                raster::ellipse(&self.bounds, style, clip, &mut f)
+            }
+            StyledRect { style, corner_radius } => {
+                raster::rounded_rect(&self.bounds, style, *corner_radius, clip, &mut f)
+            }
+            Line { start, end, stroke_width, color } => {
+                raster::segment(*start, *end, *stroke_width, Paint::Solid(*color), clip, &mut f)
+            }
+            Polyline { points, stroke_width, color } => {
+                let (points, len) = points.unpack();
+                raster::polyline(&points[..len], *stroke_width, Paint::Solid(*color), clip, &mut f)
+            }
+            QuadraticBezier { control, stroke_width, color } => {
+                raster::curve(control, *stroke_width, Paint::Solid(*color), clip, &mut f)
+            }
+            CubicBezier { control, stroke_width, color } => {
+                raster::curve(control, *stroke_width, Paint::Solid(*color), clip, &mut f)
+            }
+            Triangle { vertices, paint } => {
+                raster::polygon(vertices, *paint, clip, &mut f)
+            }
+            Polygon { points, paint } => {
+                let (points, len) = points.unpack();
+                raster::polygon(&points[..len], *paint, clip, &mut f)
+            }
+            Arc { center, radius, start, sweep, stroke_width, color } => {
+                if let Some(wedge) = raster::Wedge::new(*start, *sweep) {
+                    let thickness = Some(max(*stroke_width, 1));
+                    raster::arc(*center, *radius, thickness, wedge, Paint::Solid(*color), clip, &mut f)
+                }
+            }
+            Sector { center, radius, start, sweep, paint } => {
+                if let Some(wedge) = raster::Wedge::new(*start, *sweep) {
+                    raster::arc(*center, *radius, None, wedge, *paint, clip, &mut f)
+                }
+            }
         }
     }
 
-    // BUG: This will only work for shapes that fill their bounds,
-    // so this is totally wrong and need specialization to
-    // actually work.
     fn covers(&self, clip: &BoundingBox) -> Result<bool, RendererError> {
-        let covers = (self.bounds.x1 <= clip.x1) 
-        && (self.bounds.x2 >= clip.x2)
-        && (self.bounds.y1 <= clip.y1)
-        && (self.bounds.y2 >= clip.y2);
+        use CommandType::*;
+
+        // What is beneath shows through.
+        if !self.visible || !self.is_opaque() {
+            return Ok(false);
+        }
 
-        Ok(covers)
+        let in_bounds = self.bounds.intersection(clip) == *clip;
+
+        match &self.flavor {
+            // Polyline segments overlap at the joins so their spans
+            // can't be summed. Treat strokes as never covering.
+            Null | Line { .. } | Polyline { .. } | QuadraticBezier { .. } | CubicBezier { .. }
+            | Text { .. } => Ok(false),
+            Rect(_) => Ok(in_bounds),
+            Image(image) => Ok(in_bounds && image.is_opaque()),
+            // Hollow middles and cut corners are left out of the spans.
+            Circle(_) | Ellipse(_) | StyledRect { .. } | Triangle { .. } | Polygon { .. }
+            | Arc { .. } | Sector { .. } => {
+                if !in_bounds {
+                    return Ok(false);
+                }
+
+                // The spans of a shape never overlap so the tile is
+                // covered when they add up to its area.
+                let area = clip.width() as u64 * clip.height() as u64;
+                let mut painted = 0u64;
+                self.spans(clip, |span, _| painted += span.len() as u64);
+
+                Ok(painted == area)
+            }
+        }
      }
 
-    // BUG: This should be specialized to look if there are really
-    // any pixels to draw in the clip.So this only helps if narrow
-    // cases right now.
-    fn intersects(&self, clip: &BoundingBox) -> Result<bool, RendererError> {
-        let intersects = (self.bounds.x1 <= clip.x2) 
-        && (self.bounds.x2 >= clip.x1)
-        && (self.bounds.y1 <= clip.y2)
-        && (self.bounds.y2 >= clip.y1);
+    /// Does this command paint any pixel in `clip`? Shapes are tested
+    /// against their outline, not just their bounds.
+    pub fn intersects(&self, clip: &BoundingBox) -> Result<bool, RendererError> {
+        use CommandType::*;
+
+        if !self.visible {
+            return Ok(false);
+        }
+
+        let in_bounds = !self.bounds.intersection(clip).is_empty();
+
+        match &self.flavor {
+            Null => Ok(false),
+            Rect(_) | Text { .. } | Image(_) => Ok(in_bounds),
+            Circle(_) | Ellipse(_) | StyledRect { .. } | Line { .. } | Polyline { .. }
+            | QuadraticBezier { .. } | CubicBezier { .. } | Triangle { .. } | Polygon { .. }
+            | Arc { .. } | Sector { .. } => {
+                if !in_bounds {
+                    return Ok(false);
+                }
+
+                let mut intersects = false;
+                self.spans(clip, |_, _| intersects = true);
+
+                Ok(intersects)
+            }
+        }
+    }
+
+    /// Does `self` paint exactly the same pixels as `other` in `clip`?
+    /// This lets a changed command leave alone the tiles it didn't
+    /// change, like the start of an arc whose sweep is growing.
+    fn paints_same(&self, other: &Self, clip: &BoundingBox) -> bool {
+        use CommandType::*;
+
+        if self.blend != other.blend || self.opacity != other.opacity || self.visible != other.visible {
+            return false;
+        }
+
+        if self.bounds == other.bounds && self.flavor == other.flavor {
+            return true;
+        }
+
+        let rasterized = |command: &Self| !matches!(command.flavor, Null | Text { .. } | Image(_));
+        if !rasterized(self) || !rasterized(other) {
+            return false;
+        }
+
+        // Compare a row at a time so the spans fit in a small buffer.
+        for y in clip.y1..clip.y2 {
+            let row = BoundingBox::new(clip.x1, y, clip.x2, y + 1);
+            match (RowSpans::of(self, &row), RowSpans::of(other, &row)) {
+                (Some(a), Some(b)) if a == b => (),
+                _ => return false,
+            }
+        }
 
-        Ok(intersects)
+        true
+    }
+}
+
+/// The spans a command paints on a single row, in the order it paints
+/// them.
+#[derive(PartialEq)]
+struct RowSpans<C> {
+    spans: [(i32, i32, Paint<C>); ROW_SPANS],
+    len: usize,
+}
+
+const ROW_SPANS: usize = 2 * MAX_POINTS;
+
+impl<C: Color> RowSpans<C> {
+    /// `None` if the command paints more spans than fit.
+    fn of(command: &Command<C>, row: &BoundingBox) -> Option<Self> {
+        let mut spans = RowSpans {
+            spans: [(0, 0, Paint::Solid(C::from(Rgb { r: 0, g: 0, b: 0 }))); ROW_SPANS],
+            len: 0,
+        };
+        let mut overflow = false;
+
+        command.spans(row, |span, paint| {
+            if spans.len == ROW_SPANS {
+                overflow = true;
+            } else {
+                spans.spans[spans.len] = (span.x1, span.x2, paint);
+                spans.len += 1;
+            }
+        });
+
+        (!overflow).then_some(spans)
     }
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
-pub enum CommandType {
+pub enum CommandType<C = Rgb> {
     Null,
-    Rect(Rgb),
+    Rect(Paint<C>),
+    StyledRect {
+        style: ShapeStyle<C>,
+        corner_radius: u32,
+    },
+    Circle(ShapeStyle<C>),
+    Ellipse(ShapeStyle<C>),
+    Line {
+        start: Point,
+        end: Point,
+        stroke_width: u32,
+        color: C,
+    },
+    Polyline {
+        points: Points,
+        stroke_width: u32,
+        color: C,
+    },
+    QuadraticBezier {
+        control: [Point; 3],
+        stroke_width: u32,
+        color: C,
+    },
+    CubicBezier {
+        control: [Point; 4],
+        stroke_width: u32,
+        color: C,
+    },
+    Triangle {
+        vertices: [Point; 3],
+        paint: Paint<C>,
+    },
+    Polygon {
+        points: Points,
+        paint: Paint<C>,
+    },
+    Arc {
+        center: Point,
+        radius: u32,
+        start: i32,
+        sweep: i32,
+        stroke_width: u32,
+        color: C,
+    },
+    Sector {
+        center: Point,
+        radius: u32,
+        start: i32,
+        sweep: i32,
+        paint: Paint<C>,
+    },
+    Text {
+        text: &'static str,
+        font: Font,
+        color: C,
+    },
+    Image(&'static ImageData),
+}
+
+impl<C> CommandType<C> {
+    pub fn name(&self) -> &'static str {
+        use CommandType::*;
+
+        match self {
+            Null => "Null",
+            Rect(_) => "Rect",
+            StyledRect { .. } => "StyledRect",
+            Circle(_) => "Circle",
+            Ellipse(_) => "Ellipse",
+            Line { .. } => "Line",
+            Polyline { .. } => "Polyline",
+            QuadraticBezier { .. } => "QuadraticBezier",
+            CubicBezier { .. } => "CubicBezier",
+            Triangle { .. } => "Triangle",
+            Polygon { .. } => "Polygon",
+            Arc { .. } => "Arc",
+            Sector { .. } => "Sector",
+            Text { .. } => "Text",
+            Image(_) => "Image",
+        }
+    }
 }
 
 
@@ -98,22 +846,97 @@ pub struct Rgb {
 }
 
 impl Rgb {
-    pub fn new( r: u8, b: u8, g: u8 ) -> Self {
+    /// Red, green and blue, in that order.
+    pub const fn from_rgb( r: u8, g: u8, b: u8 ) -> Self {
+        Self {r, g, b}
+    }
+
+    /// Takes blue before green, unlike everything else.
+    #[deprecated(note = "takes red, blue, green; use `Rgb::from_rgb`, which takes red, green, blue")]
+    pub const fn new( r: u8, b: u8, g: u8 ) -> Self {
         Self {r, g, b}
     }
 }
 
-pub struct DisplayList<const LENGTH: usize> {
-    epoch: u8,
-    current: [Command; LENGTH],
-    new: [Command; LENGTH],
+/// Refers to a command in a `DisplayList` however it is moved in the
+/// stacking order. It stays valid until the command is removed, after
+/// which it is refused with `StaleHandle` even once the slot is reused.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct CommandHandle {
+    slot: usize,
+    generation: u32,
+}
+
+impl CommandHandle {
+    /// The slot the command is stored in, as used by `set`, `get` and
+    /// `update`.
+    pub fn index(&self) -> usize {
+        self.slot
+    }
+}
+
+/// Commands are stored in fixed slots, which is what `set`, `get` and
+/// `update` address, and drawn in the order given by `order`. Slots
+/// start out drawn in index order.
+///
+/// Changed tiles are tracked in `TILE_WORDS` words of bits, which
+/// `tile_words` works out for a display. The default is enough for 64
+/// tiles, a 128x128 panel drawn in 16x16 chunks. Larger displays must
+/// pass `tile_words(..)`, or every tile is looked at each frame.
+pub struct DisplayList<const LENGTH: usize, C = Rgb, const TILE_WORDS: usize = 2> {
+    current: [Command<C>; LENGTH],
+    new: [Command<C>; LENGTH],
+    /// Slots from the bottom up as last drawn.
+    current_order: [usize; LENGTH],
+    /// Slots from the bottom up as they will be drawn next.
+    order: [usize; LENGTH],
+    /// Slots written since they were last drawn.
+    dirty: [bool; LENGTH],
+    /// How many times each slot has been emptied, which handles are
+    /// checked against.
+    generations: [u32; LENGTH],
+    /// The tiles as last drawn, once there has been a draw.
+    grid: Option<Grid>,
+    /// The tiles each command in `new` might draw in, a bit each, so
+    /// tiles only look at the commands binned into them.
+    tile_index: TileIndex<LENGTH, TILE_WORDS>,
+    /// The tiles where something has changed or moved since the frame
+    /// being drawn began, and in that frame. The rest are skipped
+    /// without looking at them.
+    tiles: TileMap<TILE_WORDS>,
+    frame_tiles: TileMap<TILE_WORDS>,
+    /// Tiles the next frame, and the frame being drawn, repaint
+    /// whether or not they look changed, because a change was made
+    /// after a step had already painted them.
+    forced: TileMap<TILE_WORDS>,
+    frame_forced: TileMap<TILE_WORDS>,
+    /// The next tile of the frame being drawn, if there is one.
+    cursor: Option<usize>,
+    /// Refuse updates that change a command's flavor.
+    strict_updates: bool,
 }
 
 #[derive(Debug, PartialEq)]
 pub enum DisplayListError {
     IndexOutOfRange,
-    UpdateFlavorMismatch(CommandType, CommandType),
+    /// The slot holds no command.
+    EmptySlot,
+    /// The handle's command has been removed, and the slot may hold
+    /// another since.
+    StaleHandle,
+    /// Every slot holds a command.
+    Full,
+    /// The names of the current and new flavors, which keeps the
+    /// error the same whatever the display list's color.
+    UpdateFlavorMismatch(&'static str, &'static str),
     RenderError(RendererError),
+    CommandError(CommandError),
+}
+
+impl From<CommandError> for DisplayListError {
+    fn from(value: CommandError) -> Self {
+        DisplayListError::CommandError(value)
+    }
 }
 
 impl From<RendererError> for DisplayListError {
@@ -122,32 +945,46 @@ impl From<RendererError> for DisplayListError {
     }
 }
 
-impl<const LENGTH: usize> DisplayList<LENGTH> {
+impl<const LENGTH: usize, C: Color, const TILE_WORDS: usize> DisplayList<LENGTH, C, TILE_WORDS> {
 
     pub const LENGTH: usize = LENGTH;
 
     pub fn new() -> Self {
+        let mut order = [0; LENGTH];
+        for (position, slot) in order.iter_mut().enumerate() {
+            *slot = position;
+        }
+
         DisplayList {
-            epoch: 1,
             current: [Command::null(); LENGTH],
             new: [Command::null(); LENGTH],
+            current_order: order,
+            order,
+            dirty: [false; LENGTH],
+            generations: [0; LENGTH],
+            grid: None,
+            tile_index: TileIndex::new(),
+            tiles: TileMap::new(),
+            frame_tiles: TileMap::new(),
+            forced: TileMap::new(),
+            frame_forced: TileMap::new(),
+            cursor: None,
+            strict_updates: false,
         }
     }
 
-    pub fn set(&mut self, index: usize, mut command: Command) -> Result<(), DisplayListError> {
+    pub fn set(&mut self, index: usize, command: Command<C>) -> Result<(), DisplayListError> {
 
         if index >= LENGTH {
             return Err(DisplayListError::IndexOutOfRange);
         }
 
-        command.epoch = self.epoch;
-
-        self.new[index] = command;
+        self.write(index, command);
 
         Ok(())
     }
 
-    pub fn get(&self, index: usize) -> Result<Command, DisplayListError> {
+    pub fn get(&self, index: usize) -> Result<Command<C>, DisplayListError> {
         if index >= LENGTH {
             return Err(DisplayListError::IndexOutOfRange);
         }
@@ -155,120 +992,512 @@ impl<const LENGTH: usize> DisplayList<LENGTH> {
         Ok(self.new[index].clone())
     }
 
-    pub fn update(&mut self, index: usize, mut command: Command) -> Result<(), DisplayListError> {
+    /// Make `update` fail with `UpdateFlavorMismatch` when the new
+    /// command's flavor differs from the one on screen, as it used to.
+    pub fn set_strict_updates(&mut self, strict: bool) {
+        self.strict_updates = strict;
+    }
+
+    /// Replace the command in slot `index`, which may change its
+    /// flavor. Both where it was and where it is now are repainted.
+    pub fn update(&mut self, index: usize, command: Command<C>) -> Result<(), DisplayListError> {
 
         if index >= LENGTH {
             return Err(DisplayListError::IndexOutOfRange);
         }
 
-        let current_flavor = mem::discriminant(&self.current[index].flavor);
-        let new_flavor = mem::discriminant(&command.flavor);
+        let current_flavor = &self.current[index].flavor;
+        let new_flavor = &command.flavor;
 
-        if current_flavor != new_flavor {
-            let old = self.current[index].flavor;
-            let new = command.flavor;
-            return Err(DisplayListError::UpdateFlavorMismatch(old, new))
+        if self.strict_updates && mem::discriminant(current_flavor) != mem::discriminant(new_flavor) {
+            return Err(DisplayListError::UpdateFlavorMismatch(current_flavor.name(), new_flavor.name()))
         }
 
-        command.epoch = self.epoch;
+        self.write(index, command);
 
-        self.new[index] = command;
+        Ok(())
+    }
+
+    /// Hide or show the command in slot `index` without losing its
+    /// place.
+    pub fn set_visible(&mut self, index: usize, visible: bool) -> Result<(), DisplayListError> {
+        let command = self.get(index)?;
+        self.set(index, command.with_visible(visible))
+    }
+
+    /// The handle of the command in slot `index`.
+    pub fn handle(&self, index: usize) -> Result<CommandHandle, DisplayListError> {
+        if index >= LENGTH {
+            return Err(DisplayListError::IndexOutOfRange);
+        }
+
+        if self.is_free(index) {
+            return Err(DisplayListError::EmptySlot);
+        }
+
+        Ok(CommandHandle { slot: index, generation: self.generations[index] })
+    }
+
+    /// Where `handle` is in the stacking order, 0 being the bottom.
+    pub fn position(&self, handle: CommandHandle) -> Result<usize, DisplayListError> {
+        Ok(self.position_of(self.slot(handle)?))
+    }
+
+    /// Add `command` on top of everything else.
+    pub fn push(&mut self, command: Command<C>) -> Result<CommandHandle, DisplayListError> {
+        self.insert_at(LENGTH.saturating_sub(1), command)
+    }
+
+    /// Add `command` directly above `handle`.
+    pub fn insert_above(&mut self, handle: CommandHandle, command: Command<C>) -> Result<CommandHandle, DisplayListError> {
+        let position = self.position(handle)?;
+        let slot = self.free_slot()?;
+
+        // Taking the free slot out from below shifts `handle` down.
+        let above = if self.position_of(slot) < position {
+            position
+        } else {
+            position + 1
+        };
+        self.insert_at(above, command)
+    }
+
+    /// Add `command` directly below `handle`.
+    pub fn insert_below(&mut self, handle: CommandHandle, command: Command<C>) -> Result<CommandHandle, DisplayListError> {
+        let position = self.position(handle)?;
+        let slot = self.free_slot()?;
+
+        let below = if self.position_of(slot) < position {
+            position - 1
+        } else {
+            position
+        };
+        self.insert_at(below, command)
+    }
+
+    /// Take the command out of the list. Its slot is free for the next
+    /// insert, the tiles it was drawn in are repainted, and `handle`
+    /// and any copies of it are stale from now on.
+    pub fn remove(&mut self, handle: CommandHandle) -> Result<(), DisplayListError> {
+        let slot = self.slot(handle)?;
+        self.write(slot, Command::null());
 
         Ok(())
     }
 
-    pub async fn draw(&mut self, renderer: &mut impl Renderer) -> Result<(), DisplayListError> {
+    /// Take the command in slot `index` out of the list, as `remove`.
+    /// An empty slot is left as it is.
+    pub fn remove_at(&mut self, index: usize) -> Result<(), DisplayListError> {
+        self.set(index, Command::null())
+    }
 
-        let width = renderer.width();
-        let height = renderer.height();
-        let step = renderer.chunk_size();
+    /// Swap the command with the next command above it, if there is
+    /// one.
+    pub fn raise(&mut self, handle: CommandHandle) -> Result<(), DisplayListError> {
+        let position = self.position(handle)?;
+        let above = (position + 1..LENGTH).find(|&above| !self.is_free(self.order[above]));
 
-        for x1 in (0..width).step_by(step.0 as usize) {
-            let x2 = min(width-1, x1+step.0);
-            for y1 in (0..height).step_by(step.1 as usize) {
-                let y2 = min(height-1, y1+step.1);
+        if let Some(above) = above {
+            self.order.swap(position, above);
+            self.moved(position..=above);
+        }
 
-                let mut bottom = 0;
-                let mut has_change = false;
+        Ok(())
+    }
 
-                let bounds = BoundingBox {
-                    x1,
-                    y1,
-                    x2,
-                    y2,
-                };
+    /// Swap the command with the next command below it, if there is
+    /// one.
+    pub fn lower(&mut self, handle: CommandHandle) -> Result<(), DisplayListError> {
+        let position = self.position(handle)?;
+        let below = (0..position).rev().find(|&below| !self.is_free(self.order[below]));
 
-                renderer.set_chunk(x1, y1)?;
+        if let Some(below) = below {
+            self.order.swap(position, below);
+            self.moved(below..=position);
+        }
 
-                for i in 0..LENGTH {
-                    let current = &mut self.current[i];
-                    let new = &mut self.new[i];
+        Ok(())
+    }
 
-                    // Check for occlusions.
-                    // Dose this layer cover the tile?
-                    // If so we can start drawing from it
-                    // instead of the bottom. (Its possible
-                    // some set of tiles above 0 will cover
-                    // but we don't take advantage of that.)
-                    if new.covers(&bounds)? {
-                        bottom = i;
-                        if current.epoch == new.epoch {
-                            has_change = false;
-                        }
-                    }
+    /// Move the command to `position` in the stacking order, shifting
+    /// the ones in between.
+    pub fn move_to(&mut self, handle: CommandHandle, position: usize) -> Result<(), DisplayListError> {
+        if position >= LENGTH {
+            return Err(DisplayListError::IndexOutOfRange);
+        }
 
+        let slot = self.slot(handle)?;
+        self.move_slot(slot, position);
 
-                    // Is there change in this tile.
-                    if current.epoch != new.epoch {
+        Ok(())
+    }
 
-                        if current.intersects(&bounds)? {
-                            has_change = true
-                        } else if new.intersects(&bounds)? {
-                            has_change = true
-                        } 
+    fn move_slot(&mut self, slot: usize, position: usize) {
+        let from = self.position_of(slot);
+        if from < position {
+            self.order[from..=position].rotate_left(1);
+            self.moved(from..=position);
+        } else {
+            self.order[position..=from].rotate_right(1);
+            self.moved(position..=from);
+        }
+    }
+
+    /// The slot `handle` refers to, if it still holds its command.
+    fn slot(&self, handle: CommandHandle) -> Result<usize, DisplayListError> {
+        if handle.slot >= LENGTH {
+            return Err(DisplayListError::IndexOutOfRange);
+        }
+
+        if handle.generation != self.generations[handle.slot] {
+            return Err(DisplayListError::StaleHandle);
+        }
+
+        if self.is_free(handle.slot) {
+            return Err(DisplayListError::EmptySlot);
+        }
+
+        Ok(handle.slot)
+    }
+
+    /// Where `slot` is in the stacking order, free or not. The order
+    /// holds every slot once.
+    fn position_of(&self, slot: usize) -> usize {
+        self.order.iter()
+            .position(|&other| other == slot)
+            .unwrap_or(0)
+    }
+
+    /// Mark the tiles of the commands at `positions`, which have moved
+    /// up or down.
+    fn moved(&mut self, positions: core::ops::RangeInclusive<usize>) {
+        let Some(grid) = self.grid else {
+            return;
+        };
+
+        for position in positions {
+            self.mark(grid.bin(&self.new[self.order[position]]));
+        }
+    }
+
+    /// Mark the tiles in `bin` as changed. Part way through a frame,
+    /// the tiles it has yet to reach are painted as the list is now,
+    /// and the ones it has passed are repainted by the next frame.
+    fn mark(&mut self, bin: BoundingBox) {
+        let Some(grid) = self.grid else {
+            return;
+        };
+
+        self.tiles.mark(&grid, &bin);
+        if let Some(cursor) = self.cursor {
+            self.frame_tiles.mark(&grid, &bin);
+            self.forced.mark_before(&grid, &bin, cursor);
+        }
+    }
+
+    fn write(&mut self, slot: usize, command: Command<C>) {
+        // Emptying a slot invalidates the handles to what was in it.
+        if !self.is_free(slot) && matches!(command.flavor, CommandType::Null) {
+            self.generations[slot] = self.generations[slot].wrapping_add(1);
+        }
+
+        // Before the first draw there is nothing to bin against yet.
+        // Where it was and where it is now both need repainting.
+        if let Some(grid) = self.grid {
+            let (old, bin) = (grid.bin(&self.new[slot]), grid.bin(&command));
+            self.mark(old);
+            self.tile_index.rebin(&grid, slot, &old, &bin);
+            self.mark(bin);
+        }
+
+        self.new[slot] = command;
+        self.dirty[slot] = true;
+    }
+
+    fn is_free(&self, slot: usize) -> bool {
+        matches!(self.new[slot].flavor, CommandType::Null)
+    }
+
+    fn free_slot(&self) -> Result<usize, DisplayListError> {
+        (0..LENGTH).find(|&slot| self.is_free(slot)).ok_or(DisplayListError::Full)
+    }
+
+    /// Put `command` in a free slot and move it to `position`.
+    fn insert_at(&mut self, position: usize, command: Command<C>) -> Result<CommandHandle, DisplayListError> {
+        let slot = self.free_slot()?;
+
+        self.write(slot, command);
+        self.move_slot(slot, position);
+
+        Ok(CommandHandle { slot, generation: self.generations[slot] })
+    }
+
+    /// Has what should be in `tile` changed since it was drawn? The
+    /// commands in it are compared from the top down, stopping at one
+    /// that hides everything beneath it, so only tiles where the order
+    /// of two commands actually matters are repainted when they swap.
+    /// `slots` are the commands in it now, from the bottom up.
+    fn tile_changed(&self, index: usize, at: Point, tile: &BoundingBox, slots: &[usize]) -> Result<bool, RendererError> {
+        let mut old = LENGTH;
+        let mut new = slots.iter().rev();
+
+        loop {
+            let old_slot = self.next_below(&mut old, index, at, tile)?;
+
+            match (old_slot, new.next()) {
+                (None, None) => return Ok(false),
+                (Some(old_slot), Some(&new_slot)) if old_slot == new_slot => {
+                    let current = &self.current[new_slot];
+                    let new = &self.new[new_slot];
+
+                    // A changed command that paints the same pixels in
+                    // this tile as before doesn't change the tile.
+                    if self.dirty[new_slot] && !new.paints_same(current, tile) {
+                        return Ok(true);
+                    }
+
+                    if new.covers(tile)? {
+                        return Ok(false);
                     }
                 }
+                _ => return Ok(true),
+            }
+        }
+    }
 
-                if has_change {
+    /// The slot of the next command below `position` in the order as
+    /// last drawn that intersected `tile`, the `index`th tile at column
+    /// and row `at`. Only commands written since are not where the
+    /// tile index says.
+    fn next_below(&self, position: &mut usize, index: usize, at: Point, tile: &BoundingBox) -> Result<Option<usize>, RendererError> {
+        while *position > 0 {
+            *position -= 1;
+            let slot = self.current_order[*position];
+            let binned = match self.grid {
+                Some(grid) if self.dirty[slot] => grid.bin(&self.current[slot]).contains(at),
+                _ => self.binned(slot, index, at),
+            };
 
-                    renderer.clear()?;
+            if binned && self.current[slot].intersects(tile)? {
+                return Ok(Some(slot));
+            }
+        }
 
-                    for i in bottom..LENGTH {
-                        let command = &self.new[i];
-                        let old = &self.current[i];
-                        if command.intersects(&bounds)? {
-                            renderer.draw(command)?;
-                        } else if old.intersects(&bounds)? {
-                            renderer.draw(old)?;
-                        }
-                    }
-                    renderer.flush().await?;
+        Ok(None)
+    }
+
+    /// Is the command in `slot` binned into the `index`th tile, which
+    /// is at column and row `at`?
+    fn binned(&self, slot: usize, index: usize, at: Point) -> bool {
+        let Some(grid) = self.grid else {
+            return true;
+        };
+
+        match self.tile_index.contains(&grid, index, slot) {
+            Some(binned) => binned,
+            None => grid.bin(&self.new[slot]).contains(at),
+        }
+    }
+
+    /// Put the slots of the commands that intersect `tile`, the
+    /// `index`th tile at column and row `at`, in `slots` from the
+    /// bottom up, returning how many there are.
+    fn tile_commands(&self, index: usize, at: Point, tile: &BoundingBox, slots: &mut [usize; LENGTH]) -> Result<usize, RendererError> {
+        let mut count = 0;
+
+        for &slot in &self.order {
+            if self.binned(slot, index, at) && self.new[slot].intersects(tile)? {
+                slots[count] = slot;
+                count += 1;
+            }
+        }
+
+        Ok(count)
+    }
+
+    /// Draw the tiles that have changed since the last draw, returning
+    /// how many were painted. A frame left part way by `draw_step` or
+    /// an error is finished first.
+    pub async fn draw(&mut self, renderer: &mut impl Renderer<C>) -> Result<usize, DisplayListError> {
+        let mut painted = 0;
+
+        if self.cursor.is_some() {
+            self.paint(renderer, usize::MAX, &mut painted).await?;
+        }
+        self.paint(renderer, usize::MAX, &mut painted).await?;
+
+        Ok(painted)
+    }
+
+    /// Paint at most `budget` changed tiles, returning the tile to
+    /// carry on from or `None` once the frame is finished. Tiles are
+    /// counted down each column and then across.
+    ///
+    /// Each tile shows the list as it is when the tile is painted.
+    /// Changes made between steps show in the tiles the frame has yet
+    /// to reach, and the next frame repaints the ones it has passed.
+    /// If the renderer fails, the next step starts again from the tile
+    /// that failed.
+    pub async fn draw_step(&mut self, renderer: &mut impl Renderer<C>, budget: usize) -> Result<Option<usize>, DisplayListError> {
+        self.paint(renderer, budget, &mut 0).await
+    }
+
+    /// Paint at most `budget` more tiles of the frame, counting them in
+    /// `painted`.
+    async fn paint(&mut self, renderer: &mut impl Renderer<C>, budget: usize, painted: &mut usize) -> Result<Option<usize>, DisplayListError> {
+
+        // Nothing to do and the renderer isn't touched.
+        if self.cursor.is_none() && self.grid.is_some() && self.tiles.is_empty() {
+            return Ok(None);
+        }
+
+        let (mut index, grid) = match (self.cursor, self.grid) {
+            (Some(index), Some(grid)) => (index, grid),
+            _ => {
+                let grid = Grid::new(renderer.width(), renderer.height(), renderer.chunk_size());
+                self.begin_frame(grid);
+                (0, grid)
+            }
+        };
+        let budget = painted.saturating_add(budget);
+
+        loop {
+            index = self.frame_tiles.next(&grid, index);
+            if index >= grid.tiles() {
+                break;
+            }
+
+            if *painted == budget {
+                self.cursor = Some(index);
+                return Ok(Some(index));
+            }
+
+            let at = grid.tile_at(index);
+            let tile = grid.bounds(at);
+
+            match self.draw_tile(renderer, index, at, &tile).await {
+                Ok(true) => *painted += 1,
+                Ok(false) => (),
+                Err(error) => {
+                    // Part of the tile may have reached the screen, so
+                    // it's painted again even if the commands go back
+                    // to what they were before the retry.
+                    self.frame_forced.mark(&grid, &BoundingBox::new(at.x, at.y, at.x + 1, at.y + 1));
+                    self.cursor = Some(index);
+                    return Err(error);
                 }
             }
+            index += 1;
+        }
+
+        self.end_frame();
+
+        Ok(None)
+    }
+
+    /// Paint and flush `tile` if it has changed, returning whether it
+    /// was.
+    async fn draw_tile(
+        &self,
+        renderer: &mut impl Renderer<C>,
+        index: usize,
+        at: Point,
+        tile: &BoundingBox,
+    ) -> Result<bool, DisplayListError> {
+        renderer.set_chunk(tile.x1 as u32, tile.y1 as u32)?;
+
+        // The commands in the tile, found once for everything below.
+        // Only what is in the list now is drawn. The old version of a
+        // command that has moved away is just not there anymore.
+        let mut slots = [0; LENGTH];
+        let count = self.tile_commands(index, at, tile, &mut slots)?;
+        let slots = &slots[..count];
+
+        // Commands that blend, like an inverting highlight, depend on
+        // everything beneath them and the order they are drawn in, so a
+        // change anywhere in the tile repaints all of it.
+        if !self.frame_forced.contains(index) && !self.tile_changed(index, at, tile, slots)? {
+            return Ok(false);
         }
 
-        // update the state
-        for i in 0..LENGTH {
-            let current = &mut  self.current[i];
-            let new = &mut self.new[i];
+        // Nothing beneath what has been painted over shows, so there
+        // is nothing to clear either.
+        let (bottom, covered) = self.covered_from(tile, slots)?;
+        if !covered {
+            renderer.clear()?;
+        }
 
-            if new.epoch != current.epoch {
-                new.epoch = self.epoch;
-                *current = *new; 
+        for (drawn, &slot) in slots.iter().enumerate().skip(bottom) {
+            let command = &self.new[slot];
+            if command.is_opaque() {
+                renderer.draw(command)?;
             } else {
-                new.epoch = self.epoch;
-                current.epoch = self.epoch;
+                renderer.draw_blended(command, Below::new(&self.new, &slots[bottom..drawn]))?;
             }
         }
+        renderer.flush().await?;
 
-        // Should this happen here or at the top?
-        // What happens if we error our above should
-        // the epoch have changed? Probably not as
-        // that would mean with enough errors it would
-        // wrap which could be really confusing.
-        self.epoch = self.epoch.wrapping_add(1);
+        Ok(true)
+    }
 
-        Ok(())
+    /// Where in `slots`, the commands in `tile` from the bottom up, the
+    /// lowest that shows is, and whether the opaque commands from there
+    /// up paint all of the tile between them.
+    ///
+    /// A translucent command in among them is drawn over a backdrop
+    /// missing what is beneath, but only where an opaque command above
+    /// it paints over the result.
+    fn covered_from(&self, tile: &BoundingBox, slots: &[usize]) -> Result<(usize, bool), RendererError> {
+        let mut coverage = Coverage::new(tile);
+
+        for (position, &slot) in slots.iter().enumerate().rev() {
+            let command = &self.new[slot];
+
+            if command.covers(tile)? {
+                return Ok((position, true));
+            }
+
+            if let Some(coverage) = &mut coverage {
+                coverage.add(command);
+                if coverage.is_full() {
+                    return Ok((position, true));
+                }
+            }
+        }
+
+        Ok((0, false))
+    }
+
+    /// Start a frame that paints the tiles changed since the last.
+    fn begin_frame(&mut self, grid: Grid) {
+        // A new grid has nothing marked in it yet.
+        if self.grid != Some(grid) {
+            self.grid = Some(grid);
+            self.tile_index = TileIndex::new();
+            for slot in 0..LENGTH {
+                let bin = grid.bin(&self.new[slot]);
+                self.tile_index.rebin(&grid, slot, &BoundingBox::new(0, 0, 0, 0), &bin);
+            }
+            self.tiles.mark_all();
+        }
+
+        self.frame_tiles = self.tiles;
+        self.tiles = TileMap::new();
+        self.frame_forced = self.forced;
+        self.forced = TileMap::new();
+    }
+
+    /// Everything on screen now shows the list, apart from the forced
+    /// tiles the next frame repaints.
+    fn end_frame(&mut self) {
+        for slot in 0..LENGTH {
+            if self.dirty[slot] {
+                self.current[slot] = self.new[slot];
+                self.dirty[slot] = false;
+            }
+        }
+        self.current_order = self.order;
+        self.cursor = None;
     }
 
 }
@@ -279,13 +1508,17 @@ pub enum RendererError {
     InvalidChunkOffset{ x:u32, y:u32}
 }
 
-pub trait Renderer {
+/// Draws commands whose colors are `C`.
+pub trait Renderer<C = Rgb> {
     fn width(&self) -> u32;
     fn height(&self) -> u32;
     fn chunk_size(&self) -> (u32, u32);
     fn set_chunk(&mut self, x_index: u32, y_index: u32) -> Result<(), RendererError>;
     fn clear(&mut self) -> Result<(), RendererError>;
-    fn draw(&mut self, command: &Command) -> Result<(), RendererError>;
+    fn draw(&mut self, command: &Command<C>) -> Result<(), RendererError>;
+    /// Draw a command that isn't opaque over `below`, the commands
+    /// drawn in this chunk since it was cleared, bottom first.
+    fn draw_blended(&mut self, command: &Command<C>, below: Below<'_, C>) -> Result<(), RendererError>;
     async fn flush(&mut self) -> Result<(), RendererError>;
 }
 
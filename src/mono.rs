@@ -0,0 +1,149 @@
+use super::*;
+
+/// How colors are turned into on and off pixels on one bit panels.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MonoStrategy {
+    /// On when the luminance is at least this. Gradients fall back to
+    /// `Bayer4` so they still read as shading.
+    Threshold(u8),
+    /// 4x4 ordered dither.
+    Bayer4,
+    /// 8x8 ordered dither, finer steps at the cost of a coarser
+    /// pattern.
+    Bayer8,
+    /// Floyd–Steinberg error diffusion within each command's part of
+    /// a chunk.
+    FloydSteinberg,
+}
+
+/// `Threshold(1)`, which turns every color but black on.
+impl Default for MonoStrategy {
+    fn default() -> Self {
+        MonoStrategy::Threshold(1)
+    }
+}
+
+impl MonoStrategy {
+    /// Is `color` light enough to turn a pixel on without dithering?
+    pub(crate) fn solid_on<C: Color>(&self, color: C) -> bool {
+        let threshold = match self {
+            MonoStrategy::Threshold(threshold) => *threshold,
+            _ => 128,
+        };
+        luma(color.into()) >= threshold
+    }
+}
+
+/// Perceived brightness of `rgb` from 0 to 255 using the Rec. 601
+/// weights. Rounded up so only black is 0.
+pub(crate) fn luma(rgb: Rgb) -> u8 {
+    ((rgb.r as u32 * 299 + rgb.g as u32 * 587 + rgb.b as u32 * 114).div_ceil(1000)) as u8
+}
+
+const BAYER_4X4: [[u8; 4]; 4] = [
+    [0, 8, 2, 10],
+    [12, 4, 14, 6],
+    [3, 11, 1, 9],
+    [15, 7, 13, 5],
+];
+
+const BAYER_8X8: [[u8; 8]; 8] = [
+    [0, 32, 8, 40, 2, 34, 10, 42],
+    [48, 16, 56, 24, 50, 18, 58, 26],
+    [12, 44, 4, 36, 14, 46, 6, 38],
+    [60, 28, 52, 20, 62, 30, 54, 22],
+    [3, 35, 11, 43, 1, 33, 9, 41],
+    [51, 19, 59, 27, 49, 17, 57, 25],
+    [15, 47, 7, 39, 13, 45, 5, 37],
+    [63, 31, 55, 23, 61, 29, 53, 21],
+];
+
+/// The widest chunk error diffusion carries errors across. Pixels
+/// past it are still quantized but don't pass their error on.
+pub const MAX_DIFFUSION_WIDTH: usize = 128;
+
+/// Turns the colors of one command in one chunk into on and off
+/// pixels. Ordered dithers are anchored to screen coordinates so the
+/// pattern lines up across chunk seams. Error diffusion needs pixels
+/// in row order, so its errors are dropped whenever a command moves
+/// back up the chunk, like between the segments of a polyline.
+pub(crate) struct Quantizer {
+    strategy: MonoStrategy,
+    x0: i32,
+    row: Option<i32>,
+    errors: [[i16; MAX_DIFFUSION_WIDTH + 2]; 2],
+}
+
+impl Quantizer {
+    pub fn new(strategy: MonoStrategy, clip: &BoundingBox) -> Self {
+        Quantizer {
+            strategy,
+            x0: clip.x1,
+            row: None,
+            errors: [[0; MAX_DIFFUSION_WIDTH + 2]; 2],
+        }
+    }
+
+    /// Is `rgb` light enough to turn a pixel on when it can't be
+    /// dithered, like text which would become unreadable?
+    pub fn solid_on<C: Color>(&self, color: C) -> bool {
+        self.strategy.solid_on(color)
+    }
+
+    /// Is every pixel of `paint` on or off regardless of where it is?
+    pub fn uniform<C: Color>(&self, paint: &Paint<C>) -> Option<bool> {
+        match (self.strategy, paint) {
+            (MonoStrategy::Threshold(_), Paint::Solid(color)) => Some(self.solid_on(*color)),
+            _ => None,
+        }
+    }
+
+    /// Should the pixel at `x`, `y` painted with `paint` be on?
+    pub fn on<C: Color>(&mut self, paint: &Paint<C>, x: i32, y: i32) -> bool {
+        let rgb: Rgb = paint.color_at(x, y).into();
+        let value = luma(rgb) as u32;
+
+        match self.strategy {
+            MonoStrategy::Threshold(_) if matches!(paint, Paint::Solid(_)) => self.solid_on(rgb),
+            MonoStrategy::Threshold(_) | MonoStrategy::Bayer4 => {
+                let cell = BAYER_4X4[y.rem_euclid(4) as usize][x.rem_euclid(4) as usize] as u32;
+                value > cell * 16 + 8
+            }
+            MonoStrategy::Bayer8 => {
+                let cell = BAYER_8X8[y.rem_euclid(8) as usize][x.rem_euclid(8) as usize] as u32;
+                value > cell * 4 + 2
+            }
+            MonoStrategy::FloydSteinberg => self.diffuse(value as i16, x, y),
+        }
+    }
+
+    fn diffuse(&mut self, value: i16, x: i32, y: i32) -> bool {
+        match self.row {
+            Some(row) if row == y => (),
+            Some(row) if row + 1 == y => {
+                self.errors[0] = self.errors[1];
+                self.errors[1] = [0; MAX_DIFFUSION_WIDTH + 2];
+            }
+            _ => self.errors = [[0; MAX_DIFFUSION_WIDTH + 2]; 2],
+        }
+        self.row = Some(y);
+
+        // Shifted by one so the pixel to the left of the chunk has
+        // somewhere to go.
+        let i = (x - self.x0) as usize + 1;
+        if i > MAX_DIFFUSION_WIDTH {
+            return value >= 128;
+        }
+
+        let value = value + self.errors[0][i];
+        let on = value >= 128;
+        let error = value - if on { 255 } else { 0 };
+
+        self.errors[0][i + 1] += error * 7 / 16;
+        self.errors[1][i - 1] += error * 3 / 16;
+        self.errors[1][i] += error * 5 / 16;
+        self.errors[1][i + 1] += error / 16;
+
+        on
+    }
+}
@@ -0,0 +1,57 @@
+use super::*;
+
+/// The widest and tallest tile that coverage is tracked for a pixel
+/// at a time. Bigger tiles only count commands that cover all of it.
+pub(crate) const MAX_COVERAGE: u32 = 64;
+
+/// Which pixels of a tile are painted over by the opaque commands seen
+/// so far, a bit per pixel and a word per row.
+pub(crate) struct Coverage {
+    tile: BoundingBox,
+    rows: [u64; MAX_COVERAGE as usize],
+}
+
+impl Coverage {
+    pub(crate) fn new(tile: &BoundingBox) -> Option<Self> {
+        let (width, height) = (tile.width(), tile.height());
+        if width > MAX_COVERAGE || height > MAX_COVERAGE {
+            return None;
+        }
+
+        Some(Self { tile: *tile, rows: [0; MAX_COVERAGE as usize] })
+    }
+
+    /// Add what `command` paints, if nothing beneath shows through it.
+    pub(crate) fn add<C: Color>(&mut self, command: &Command<C>) {
+        use CommandType::*;
+
+        if !command.visible || !command.is_opaque() {
+            return;
+        }
+
+        let tile = self.tile;
+        match &command.flavor {
+            Null => (),
+            Text { .. } | Image(_) => command.pixels(&tile, |x, y, _| {
+                self.rows[(y - tile.y1) as usize] |= 1 << (x - tile.x1);
+            }),
+            _ => command.spans(&tile, |span, _| {
+                self.rows[(span.y - tile.y1) as usize] |= run((span.x1 - tile.x1) as u32, (span.x2 - tile.x1) as u32);
+            }),
+        }
+    }
+
+    /// Is every pixel of the tile painted?
+    pub(crate) fn is_full(&self) -> bool {
+        let (width, height) = (self.tile.width(), self.tile.height());
+        let full = run(0, width);
+
+        self.rows[..height as usize].iter().all(|&row| row == full)
+    }
+}
+
+/// Bits `x1..x2` set.
+fn run(x1: u32, x2: u32) -> u64 {
+    let below = |x: u32| if x >= u64::BITS { u64::MAX } else { (1 << x) - 1 };
+    below(x2) & !below(x1)
+}
@@ -0,0 +1,78 @@
+use core::cmp::min;
+
+use super::*;
+
+/// What a fill is painted with. Gradients are positioned in screen
+/// coordinates and blend from `from` to `to`. Their geometry is kept
+/// narrow so the paint, and the commands holding it, shrink with `C`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Paint<C = Rgb> {
+    Solid(C),
+    /// `from` at `start` to `to` at `end`, constant along lines at
+    /// right angles to the gradient.
+    LinearGradient {
+        start: ShortPoint,
+        end: ShortPoint,
+        from: C,
+        to: C,
+    },
+    /// `from` at `center` to `to` at `radius` pixels out and beyond.
+    RadialGradient {
+        center: ShortPoint,
+        radius: u16,
+        from: C,
+        to: C,
+    },
+}
+
+impl<C: Color> From<C> for Paint<C> {
+    fn from(value: C) -> Self {
+        Paint::Solid(value)
+    }
+}
+
+impl<C: Color> Paint<C> {
+    /// The color of the pixel at `x`, `y`.
+    pub fn color_at(&self, x: i32, y: i32) -> C {
+        match *self {
+            Paint::Solid(color) => color,
+            Paint::LinearGradient { start, end, from, to } => {
+                let (dx, dy) = (end.x as i64 - start.x as i64, end.y as i64 - start.y as i64);
+                let (px, py) = (x as i64 - start.x as i64, y as i64 - start.y as i64);
+                let length = dx * dx + dy * dy;
+
+                let t = if length == 0 {
+                    256
+                } else {
+                    ((px * dx + py * dy) * 256 / length).clamp(0, 256)
+                };
+                C::from(mix(from.into(), to.into(), t as u32))
+            }
+            Paint::RadialGradient { center, radius, from, to } => {
+                let (dx, dy) = (x as i64 - center.x as i64, y as i64 - center.y as i64);
+                let distance = ((dx * dx + dy * dy) as u64).isqrt();
+
+                let t = if radius == 0 {
+                    256
+                } else {
+                    min(distance * 256 / radius as u64, 256)
+                };
+                C::from(mix(from.into(), to.into(), t as u32))
+            }
+        }
+    }
+}
+
+/// `from` blended towards `to` by `t / 256`.
+fn mix(from: Rgb, to: Rgb, t: u32) -> Rgb {
+    let channel = |a: u8, b: u8| {
+        let (a, b) = (a as i32, b as i32);
+        (a + (b - a) * t as i32 / 256) as u8
+    };
+
+    Rgb {
+        r: channel(from.r, to.r),
+        g: channel(from.g, to.g),
+        b: channel(from.b, to.b),
+    }
+}
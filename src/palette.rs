@@ -0,0 +1,114 @@
+use core::fmt;
+use core::marker::PhantomData;
+
+use embedded_graphics::pixelcolor::{BinaryColor, Gray2, Gray4, Gray8, Rgb565, Rgb888};
+
+use super::*;
+
+/// How many colors a `Palette` has, so an index fits in 4 bits.
+pub const PALETTE_SIZE: usize = 16;
+
+/// A fixed set of colors for `Indexed`. Implement it on an empty type
+/// for each palette:
+///
+/// ```
+/// use blitty::{Indexed, Palette, Rgb};
+///
+/// #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+/// struct Ui;
+///
+/// impl Palette for Ui {
+///     const COLORS: [Rgb; 16] = [Rgb::BLACK; 16];
+/// }
+///
+/// let background = Indexed::<Ui>::new(0);
+/// ```
+pub trait Palette: Copy + Eq + fmt::Debug {
+    const COLORS: [Rgb; PALETTE_SIZE];
+}
+
+/// A color stored as an index into the palette `P`. Commands only
+/// hold the index and the renderer looks the color up when drawing,
+/// which keeps display lists small.
+pub struct Indexed<P> {
+    index: u8,
+    palette: PhantomData<P>,
+}
+
+impl<P: Palette> Indexed<P> {
+    /// The color at `index`, which wraps at `PALETTE_SIZE`.
+    pub const fn new( index: u8 ) -> Self {
+        Indexed {
+            index: index % PALETTE_SIZE as u8,
+            palette: PhantomData,
+        }
+    }
+
+    pub const fn index(&self) -> u8 {
+        self.index
+    }
+}
+
+// Derives would require `P` itself to be `Copy` and so on.
+impl<P> Clone for Indexed<P> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<P> Copy for Indexed<P> {}
+
+impl<P> PartialEq for Indexed<P> {
+    fn eq(&self, other: &Self) -> bool {
+        self.index == other.index
+    }
+}
+
+impl<P> Eq for Indexed<P> {}
+
+impl<P> fmt::Debug for Indexed<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Indexed").field(&self.index).finish()
+    }
+}
+
+impl<P: Palette> From<Indexed<P>> for Rgb {
+    fn from(value: Indexed<P>) -> Self {
+        P::COLORS[value.index as usize]
+    }
+}
+
+/// The closest color in the palette, which is what gradients and
+/// blending end up as.
+impl<P: Palette> From<Rgb> for Indexed<P> {
+    fn from(value: Rgb) -> Self {
+        let distance = |color: &Rgb| {
+            let channel = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+            channel(color.r, value.r) + channel(color.g, value.g) + channel(color.b, value.b)
+        };
+
+        let mut closest = 0;
+        for (index, color) in P::COLORS.iter().enumerate() {
+            if distance(color) < distance(&P::COLORS[closest]) {
+                closest = index;
+            }
+        }
+
+        Indexed::new(closest as u8)
+    }
+}
+
+// Lets `EmbeddedRender` look up the index when it draws.
+macro_rules! resolve_into {
+    ($($target:ty),*) => {
+        $(
+            impl<P: Palette> From<Indexed<P>> for $target {
+                fn from(value: Indexed<P>) -> Self {
+                    Rgb::from(value).into()
+                }
+            }
+        )*
+    };
+}
+
+resolve_into!(BinaryColor, Gray2, Gray4, Gray8, Rgb565, Rgb888);
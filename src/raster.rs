@@ -0,0 +1,557 @@
+// Integer scanline rasterizers shared by the renderers and the
+// display list occlusion tests. Everything here works on half open
+// ranges, `x1..x2` by `y1..y2`, and avoids floating point so it can
+// run on targets without an FPU.
+
+use core::cmp::{min, max};
+
+use super::*;
+
+/// A horizontal run of pixels `x1..x2` on row `y`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) struct Span {
+    pub y: i32,
+    pub x1: i32,
+    pub x2: i32,
+}
+
+impl Span {
+    pub fn len(&self) -> u32 {
+        self.x2.abs_diff(self.x1)
+    }
+}
+
+/// Emit `x1..x2` on row `y` clamped to `clip` if anything is left.
+pub(crate) fn emit<C: Color>(
+    clip: &BoundingBox,
+    y: i32,
+    x1: i64,
+    x2: i64,
+    paint: Paint<C>,
+    f: &mut impl FnMut(Span, Paint<C>),
+) {
+    let x1 = max(x1, clip.x1 as i64);
+    let x2 = min(x2, clip.x2 as i64);
+    if x1 < x2 {
+        f(Span { y, x1: x1 as i32, x2: x2 as i32 }, paint);
+    }
+}
+
+/// Rows shared by `bounds` and `clip`.
+pub(crate) fn rows(bounds: &BoundingBox, clip: &BoundingBox) -> core::ops::Range<i32> {
+    max(bounds.y1, clip.y1)..min(bounds.y2, clip.y2)
+}
+
+/// The horizontal extent of the ellipse inscribed in `bounds` on
+/// row `y`, sampled at pixel centers.
+///
+/// Works in doubled coordinates so the center of a pixel is an
+/// integer: a pixel is inside when
+/// `dx^2 * h^2 + dy^2 * w^2 <= w^2 * h^2`.
+pub(crate) fn ellipse_row(bounds: &BoundingBox, y: i32) -> Option<(i64, i64)> {
+    if bounds.x2 <= bounds.x1 || bounds.y2 <= bounds.y1 {
+        return None;
+    }
+    if y < bounds.y1 || y >= bounds.y2 {
+        return None;
+    }
+
+    let w = (bounds.x2 - bounds.x1) as i64;
+    let h = (bounds.y2 - bounds.y1) as i64;
+    let dy = 2 * y as i64 + 1 - (bounds.y1 + bounds.y2) as i64;
+
+    let t = w * w * (h * h - dy * dy);
+    if t < 0 {
+        return None;
+    }
+    let k = ((t / (h * h)) as u64).isqrt() as i64;
+
+    // |2x + 1 - (x1 + x2)| <= k
+    let s = (bounds.x1 + bounds.x2) as i64 - 1;
+    let x1 = (s - k + 1).div_euclid(2);
+    let x2 = (s + k).div_euclid(2) + 1;
+
+    if x1 < x2 {
+        Some((x1, x2))
+    } else {
+        None
+    }
+}
+
+/// `bounds` shrunk by `inset` on every side, if anything is left.
+pub(crate) fn inset(bounds: &BoundingBox, inset: u32) -> Option<BoundingBox> {
+    let inset = inset as i64;
+    let x1 = bounds.x1 as i64 + inset;
+    let y1 = bounds.y1 as i64 + inset;
+    let x2 = bounds.x2 as i64 - inset;
+    let y2 = bounds.y2 as i64 - inset;
+
+    if x1 < x2 && y1 < y2 {
+        Some(BoundingBox::new(x1 as i32, y1 as i32, x2 as i32, y2 as i32))
+    } else {
+        None
+    }
+}
+
+/// The horizontal extent on row `y` of `bounds` with its corners
+/// rounded off by quarter circles of `radius`.
+pub(crate) fn rounded_row(bounds: &BoundingBox, radius: u32, y: i32) -> Option<(i64, i64)> {
+    if bounds.x2 <= bounds.x1 || y < bounds.y1 || y >= bounds.y2 {
+        return None;
+    }
+
+    let radius = min(radius, min(bounds.width(), bounds.height()) / 2) as i32;
+    let (x1, x2) = (bounds.x1 as i64, bounds.x2 as i64);
+
+    let corner = if y < bounds.y1 + radius {
+        BoundingBox::new(bounds.x1, bounds.y1, bounds.x1 + 2 * radius, bounds.y1 + 2 * radius)
+    } else if y >= bounds.y2 - radius {
+        BoundingBox::new(bounds.x1, bounds.y2 - 2 * radius, bounds.x1 + 2 * radius, bounds.y2)
+    } else {
+        return Some((x1, x2));
+    };
+
+    // Mirror the left edge of the corner circle onto the right.
+    let (cx1, _) = ellipse_row(&corner, y)?;
+    let cut = cx1 - x1;
+    Some((x1 + cut, x2 - cut))
+}
+
+/// Spans of a convex outline that fall in `clip`. `row` gives the
+/// extent of the shape on a row for the outer bounds, with an inset
+/// of 0, and for the bounds shrunk by the stroke. The stroke is drawn
+/// inside the bounds so the stroke and fill spans never overlap.
+fn outlined<C: Color>(
+    bounds: &BoundingBox,
+    style: &ShapeStyle<C>,
+    clip: &BoundingBox,
+    f: &mut impl FnMut(Span, Paint<C>),
+    row: impl Fn(&BoundingBox, u32, i32) -> Option<(i64, i64)>,
+) {
+    let inner = match style.stroke {
+        Some(_) => inset(bounds, style.stroke_width),
+        None => Some(*bounds),
+    };
+
+    for y in rows(bounds, clip) {
+        let Some((ox1, ox2)) = row(bounds, 0, y) else {
+            continue;
+        };
+
+        let hole = inner.and_then(|inner| row(&inner, style.stroke_width, y));
+
+        match hole {
+            Some((ix1, ix2)) => {
+                if let Some(stroke) = style.stroke {
+                    emit(clip, y, ox1, ix1, Paint::Solid(stroke), f);
+                    emit(clip, y, ix2, ox2, Paint::Solid(stroke), f);
+                }
+                if let Some(fill) = style.fill {
+                    emit(clip, y, ix1, ix2, fill, f);
+                }
+            }
+            None => {
+                if let Some(stroke) = style.stroke {
+                    emit(clip, y, ox1, ox2, Paint::Solid(stroke), f);
+                }
+            }
+        }
+    }
+}
+
+/// Spans of the ellipse inscribed in `bounds` that fall in `clip`.
+pub(crate) fn ellipse<C: Color>(
+    bounds: &BoundingBox,
+    style: &ShapeStyle<C>,
+    clip: &BoundingBox,
+    f: &mut impl FnMut(Span, Paint<C>),
+) {
+    outlined(bounds, style, clip, f, |bounds, _, y| ellipse_row(bounds, y))
+}
+
+/// Spans of a rectangle with rounded corners that fall in `clip`.
+/// The inside of the stroke has its radius reduced by the stroke
+/// width so the stroke keeps an even width around the corners.
+pub(crate) fn rounded_rect<C: Color>(
+    bounds: &BoundingBox,
+    style: &ShapeStyle<C>,
+    radius: u32,
+    clip: &BoundingBox,
+    f: &mut impl FnMut(Span, Paint<C>),
+) {
+    outlined(bounds, style, clip, f, |bounds, inset, y| {
+        rounded_row(bounds, radius.saturating_sub(inset), y)
+    })
+}
+
+/// The bounds of a stroke of `width` pixels through `points`.
+pub(crate) fn stroke_bounds(points: &[Point], width: u32) -> BoundingBox {
+    let reach = (width / 2) as i32;
+
+    let mut bounds = BoundingBox::new(i32::MAX, i32::MAX, i32::MIN, i32::MIN);
+    for point in points {
+        bounds.x1 = min(bounds.x1, point.x - reach);
+        bounds.y1 = min(bounds.y1, point.y - reach);
+        bounds.x2 = max(bounds.x2, point.x + reach + 1);
+        bounds.y2 = max(bounds.y2, point.y + reach + 1);
+    }
+
+    if points.is_empty() {
+        BoundingBox::new(0, 0, 0, 0)
+    } else {
+        bounds
+    }
+}
+
+/// Is the center of pixel `x`, `y` within `width / 2` of the segment
+/// from `a` to `b`? Like `ellipse_row` this works in doubled
+/// coordinates so pixel centers are integers.
+fn near_segment(a: Point, b: Point, width: u32, x: i32, y: i32) -> bool {
+    let (ax, ay) = (2 * a.x as i64 + 1, 2 * a.y as i64 + 1);
+    let (bx, by) = (2 * b.x as i64 + 1, 2 * b.y as i64 + 1);
+    let (px, py) = (2 * x as i64 + 1, 2 * y as i64 + 1);
+
+    // A zero width stroke would paint nothing so treat it as a hairline.
+    let reach = max(width, 1) as i64;
+    let reach = reach * reach;
+
+    let (dx, dy) = (bx - ax, by - ay);
+    let (ux, uy) = (px - ax, py - ay);
+    let length = dx * dx + dy * dy;
+    let along = ux * dx + uy * dy;
+
+    if along <= 0 {
+        ux * ux + uy * uy <= reach
+    } else if along >= length {
+        let (vx, vy) = (px - bx, py - by);
+        vx * vx + vy * vy <= reach
+    } else {
+        let across = ux * dy - uy * dx;
+        across * across <= reach * length
+    }
+}
+
+/// Spans of a round capped stroke from `a` to `b` that fall in `clip`.
+pub(crate) fn segment<C: Color>(
+    a: Point,
+    b: Point,
+    width: u32,
+    paint: Paint<C>,
+    clip: &BoundingBox,
+    f: &mut impl FnMut(Span, Paint<C>),
+) {
+    let bounds = stroke_bounds(&[a, b], width);
+    let x_start = max(bounds.x1, clip.x1);
+    let x_end = min(bounds.x2, clip.x2);
+
+    for y in rows(&bounds, clip) {
+        // The stroke is convex so each row is a single run.
+        let Some(x1) = (x_start..x_end).find(|&x| near_segment(a, b, width, x, y)) else {
+            continue;
+        };
+        let x2 = (x1..x_end)
+            .rev()
+            .find(|&x| near_segment(a, b, width, x, y))
+            .unwrap_or(x1);
+
+        emit(clip, y, x1 as i64, x2 as i64 + 1, paint, f);
+    }
+}
+
+/// Spans of a stroke through `points`. Joins are round because every
+/// segment has round caps, so neighbouring segments emit overlapping
+/// spans around the shared point.
+pub(crate) fn polyline<C: Color>(
+    points: &[Point],
+    width: u32,
+    paint: Paint<C>,
+    clip: &BoundingBox,
+    f: &mut impl FnMut(Span, Paint<C>),
+) {
+    match points {
+        [] => (),
+        [point] => segment(*point, *point, width, paint, clip, f),
+        _ => {
+            for pair in points.windows(2) {
+                segment(pair[0], pair[1], width, paint, clip, f);
+            }
+        }
+    }
+}
+
+/// The bounds of a filled polygon. Vertices sit on pixel corners so a
+/// polygon through the corners of a `BoundingBox` paints the same
+/// pixels as a rect with those bounds.
+pub(crate) fn polygon_bounds(points: &[Point]) -> BoundingBox {
+    let mut bounds = BoundingBox::new(i32::MAX, i32::MAX, i32::MIN, i32::MIN);
+    for point in points {
+        bounds.x1 = min(bounds.x1, point.x);
+        bounds.y1 = min(bounds.y1, point.y);
+        bounds.x2 = max(bounds.x2, point.x);
+        bounds.y2 = max(bounds.y2, point.y);
+    }
+
+    if points.is_empty() {
+        BoundingBox::new(0, 0, 0, 0)
+    } else {
+        bounds
+    }
+}
+
+/// `ceil(n / d)` for a positive `d`.
+fn div_ceil(n: i64, d: i64) -> i64 {
+    -((-n).div_euclid(d))
+}
+
+/// Spans of the polygon through `points` that fall in `clip`, filled
+/// with the even-odd rule and sampled at pixel centers.
+///
+/// In doubled coordinates vertices are even and pixel centers odd, so
+/// a scanline never passes exactly through a vertex.
+pub(crate) fn polygon<C: Color>(
+    points: &[Point],
+    paint: Paint<C>,
+    clip: &BoundingBox,
+    f: &mut impl FnMut(Span, Paint<C>),
+) {
+    let bounds = polygon_bounds(points);
+
+    for y in rows(&bounds, clip) {
+        let sample = 2 * y as i64 + 1;
+
+        let mut crossings = [0i64; MAX_POINTS];
+        let mut count = 0;
+
+        for (i, a) in points.iter().enumerate() {
+            let b = points[(i + 1) % points.len()];
+            let (ax, ay) = (2 * a.x as i64, 2 * a.y as i64);
+            let (bx, by) = (2 * b.x as i64, 2 * b.y as i64);
+
+            if (sample < ay) == (sample < by) {
+                continue;
+            }
+
+            // Where the edge crosses the scanline is `n / d`. The first
+            // pixel whose center is at or right of it is
+            // `ceil((n / d - 1) / 2)`.
+            let (n, d) = ((sample - ay) * (bx - ax) + ax * (by - ay), by - ay);
+            let (n, d) = if d < 0 { (-n, -d) } else { (n, d) };
+
+            crossings[count] = div_ceil(n - d, 2 * d);
+            count += 1;
+        }
+
+        let crossings = &mut crossings[..count];
+        crossings.sort_unstable();
+
+        for pair in crossings.chunks_exact(2) {
+            emit(clip, y, pair[0], pair[1], paint, f);
+        }
+    }
+}
+
+/// `sin(degrees) * 2^14` for 0 to 90 degrees so arcs can be drawn
+/// without floating point.
+const SINE: [i64; 91] = [
+    0, 286, 572, 857, 1143, 1428, 1713, 1997, 2280, 2563,
+    2845, 3126, 3406, 3686, 3964, 4240, 4516, 4790, 5063, 5334,
+    5604, 5872, 6138, 6402, 6664, 6924, 7182, 7438, 7692, 7943,
+    8192, 8438, 8682, 8923, 9162, 9397, 9630, 9860, 10087, 10311,
+    10531, 10749, 10963, 11174, 11381, 11585, 11786, 11982, 12176, 12365,
+    12551, 12733, 12911, 13085, 13255, 13421, 13583, 13741, 13894, 14044,
+    14189, 14330, 14466, 14598, 14726, 14849, 14968, 15082, 15191, 15296,
+    15396, 15491, 15582, 15668, 15749, 15826, 15897, 15964, 16026, 16083,
+    16135, 16182, 16225, 16262, 16294, 16322, 16344, 16362, 16374, 16382,
+    16384,
+];
+
+/// `(cos, sin)` of `degrees` scaled by 2^14. Zero degrees points
+/// right and, since y grows down, angles turn clockwise on screen.
+fn direction(degrees: i32) -> (i64, i64) {
+    let degrees = degrees.rem_euclid(360) as usize;
+    let (quadrant, angle) = (degrees / 90, degrees % 90);
+    let (sin, cos) = (SINE[angle], SINE[90 - angle]);
+
+    match quadrant {
+        0 => (cos, sin),
+        1 => (-sin, cos),
+        2 => (-cos, -sin),
+        _ => (sin, -cos),
+    }
+}
+
+/// The directions swept from `start` through `sweep` degrees.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Wedge {
+    start: (i64, i64),
+    end: (i64, i64),
+    sweep: i32,
+}
+
+impl Wedge {
+    pub fn new(start: i32, sweep: i32) -> Option<Self> {
+        if sweep == 0 {
+            return None;
+        }
+
+        // Sweeping backwards covers the same directions as sweeping
+        // forwards from the far end.
+        let (start, sweep) = if sweep < 0 {
+            (start + sweep, -sweep)
+        } else {
+            (start, sweep)
+        };
+
+        Some(Wedge {
+            start: direction(start),
+            end: direction(start + sweep),
+            sweep,
+        })
+    }
+
+    /// Is the offset `dx`, `dy` from the center inside the wedge?
+    fn contains(&self, dx: i64, dy: i64) -> bool {
+        if self.sweep >= 360 {
+            return true;
+        }
+
+        // Positive when the offset is clockwise of the direction.
+        let cross = |(x, y): (i64, i64)| x * dy - y * dx;
+        let after_start = cross(self.start) >= 0;
+        let before_end = cross(self.end) <= 0;
+
+        if self.sweep > 180 {
+            after_start || before_end
+        } else {
+            after_start && before_end
+        }
+    }
+}
+
+/// The bounds of a circle of `radius` pixels around the center of
+/// the pixel at `center`.
+pub(crate) fn arc_bounds(center: Point, radius: u32) -> BoundingBox {
+    BoundingBox::new(
+        center.x - radius as i32,
+        center.y - radius as i32,
+        center.x + radius as i32 + 1,
+        center.y + radius as i32 + 1,
+    )
+}
+
+/// Spans of the part of a circle of `radius` around `center` that is
+/// inside `wedge` and falls in `clip`. With a `thickness` only the
+/// outer ring of that many pixels is painted, otherwise the whole pie
+/// slice is.
+pub(crate) fn arc<C: Color>(
+    center: Point,
+    radius: u32,
+    thickness: Option<u32>,
+    wedge: Wedge,
+    paint: Paint<C>,
+    clip: &BoundingBox,
+    f: &mut impl FnMut(Span, Paint<C>),
+) {
+    let bounds = arc_bounds(center, radius);
+    let (cx, cy) = (2 * center.x as i64 + 1, 2 * center.y as i64 + 1);
+
+    let outer = 2 * radius as i64;
+    let outer = outer * outer;
+    let inner = thickness.map(|thickness| {
+        let inner = 2 * radius.saturating_sub(thickness) as i64;
+        inner * inner
+    });
+
+    let x_start = max(bounds.x1, clip.x1);
+    let x_end = min(bounds.x2, clip.x2);
+
+    for y in rows(&bounds, clip) {
+        let dy = 2 * y as i64 + 1 - cy;
+        let mut run = None;
+
+        for x in x_start..x_end {
+            let dx = 2 * x as i64 + 1 - cx;
+            let distance = dx * dx + dy * dy;
+            let inside = distance <= outer
+                && inner.is_none_or(|inner| distance > inner)
+                && wedge.contains(dx, dy);
+
+            match (inside, run) {
+                (true, None) => run = Some(x),
+                (false, Some(x1)) => {
+                    emit(clip, y, x1 as i64, x as i64, paint, f);
+                    run = None;
+                }
+                _ => (),
+            }
+        }
+
+        if let Some(x1) = run {
+            emit(clip, y, x1 as i64, x_end as i64, paint, f);
+        }
+    }
+}
+
+/// The most line segments a Bézier curve is flattened into.
+pub(crate) const MAX_CURVE_SEGMENTS: usize = 32;
+
+/// Points along the Bézier curve with `control` points, flattened in
+/// fixed point. The number of segments grows with the length of the
+/// control polygon, so small curves stay cheap. Returns the points
+/// and how many of them are used.
+pub(crate) fn flatten(control: &[Point]) -> ([Point; MAX_CURVE_SEGMENTS + 1], usize) {
+    let mut points = [Point::new(0, 0); MAX_CURVE_SEGMENTS + 1];
+
+    let hull: u64 = control
+        .windows(2)
+        .map(|pair| max(pair[0].x.abs_diff(pair[1].x), pair[0].y.abs_diff(pair[1].y)) as u64)
+        .sum();
+    let segments = (hull / 4).clamp(2, MAX_CURVE_SEGMENTS as u64) as i64;
+
+    // Bernstein weights at t = i / segments, all scaled by
+    // segments^degree so the sums stay in integers.
+    let degree = control.len() as u32 - 1;
+    let scale = segments.pow(degree);
+    let binomial = |k: u32| match (degree, k) {
+        (3, 1) | (3, 2) => 3,
+        (2, 1) => 2,
+        _ => 1,
+    };
+
+    for i in 0..=segments {
+        let (mut x, mut y) = (0i64, 0i64);
+        for (k, point) in control.iter().enumerate() {
+            let k = k as u32;
+            let weight = binomial(k) * (segments - i).pow(degree - k) * i.pow(k);
+            x += weight * point.x as i64;
+            y += weight * point.y as i64;
+        }
+
+        points[i as usize] = Point::new(
+            (x + scale / 2).div_euclid(scale) as i32,
+            (y + scale / 2).div_euclid(scale) as i32,
+        );
+    }
+
+    (points, segments as usize + 1)
+}
+
+/// The bounds of a `width` stroke along a Bézier curve. This follows
+/// the flattened curve rather than the control points, which can be
+/// far outside it.
+pub(crate) fn curve_bounds(control: &[Point], width: u32) -> BoundingBox {
+    let (points, len) = flatten(control);
+    stroke_bounds(&points[..len], width)
+}
+
+/// Spans of a `width` stroke along a Bézier curve.
+pub(crate) fn curve<C: Color>(
+    control: &[Point],
+    width: u32,
+    paint: Paint<C>,
+    clip: &BoundingBox,
+    f: &mut impl FnMut(Span, Paint<C>),
+) {
+    let (points, len) = flatten(control);
+    polyline(&points[..len], width, paint, clip, f)
+}
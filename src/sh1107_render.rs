@@ -3,6 +3,8 @@ use display_interface::{AsyncWriteOnlyDataCommand, DisplayError, DataFormat};
 use core::cmp::{min, max};
 
 use super::*;
+use raster::Span;
+use mono::Quantizer;
 
 mod commands;
 use commands as i2c;
@@ -27,6 +29,7 @@ where
     clip: BoundingBox,
     buffer: [u8; BUFFER_SIZE],
     display: &'a mut DI,
+    mono: MonoStrategy,
 }
 
 impl<'a, DI, const BUFFER_SIZE: usize> Sh1107Render<'a, DI, BUFFER_SIZE>  
@@ -39,9 +42,119 @@ where
             height,
             chunk_width,
             chunk_height,
-            clip: BoundingBox::new(0, 0, chunk_width, chunk_height),
+            clip: BoundingBox::new(0, 0, chunk_width as i32, chunk_height as i32),
             buffer: [0u8;BUFFER_SIZE],
             display,
+            mono: MonoStrategy::default(),
+        }
+    }
+
+    /// Choose how colors are turned into on and off pixels.
+    pub fn set_mono_strategy(&mut self, mono: MonoStrategy) {
+        self.mono = mono;
+    }
+
+    pub fn get_display(&self) -> &DI {
+        self.display
+    }
+
+    pub fn get_display_mut(&mut self) -> &mut DI {
+        self.display
+    }
+
+    // Each byte is a 8 pixel high column with the fist chunk_width bytes
+    // being row 0-7 and each consecutive chunk_width bytes being the
+    // next 8 row.
+    fn fill_span<C: Color>(&mut self, quantizer: &mut Quantizer, span: Span, paint: Paint<C>) {
+        let uniform = quantizer.uniform(&paint);
+
+        // Offset from chunk top
+        let y = (span.y - self.clip.y1) as u32;
+        let row = (y / 8) * self.chunk_width;
+        let set_bit = 1u8 << (y % 8);
+
+        for x in span.x1..span.x2 {
+            let on = uniform.unwrap_or_else(|| quantizer.on(&paint, x, span.y));
+
+            // Offset from chunk left
+            let byte = &mut self.buffer[(row + (x - self.clip.x1) as u32) as usize];
+            if on {
+                *byte |= set_bit;
+            } else {
+                *byte &= !set_bit;
+            }
+        }
+    }
+
+    fn flip(&mut self, x: i32, y: i32) {
+        let (x, y) = ((x - self.clip.x1) as u32, (y - self.clip.y1) as u32);
+        self.buffer[((y / 8) * self.chunk_width + x) as usize] ^= 1u8 << (y % 8);
+    }
+
+    // Flip the pixels `command` paints in the chunk where `flip` says
+    // so, each once. Strokes overlap at joins and between curve
+    // segments, so flipping pixels as they come would undo some, and
+    // a row is gathered into a mask first.
+    fn flip_once<C: Color>(&mut self, command: &Command<C>, mut flip: impl FnMut(C) -> bool) {
+        let area = command.bounds.intersection(&self.clip);
+        if area.is_empty() {
+            return;
+        }
+
+        for y in area.y1..area.y2 {
+            for x1 in (area.x1..area.x2).step_by(64) {
+                let row = BoundingBox::new(x1, y, min(x1 + 64, area.x2), y + 1);
+                let mut mask = 0u64;
+                command.pixels(&row, |x, _, color| {
+                    if flip(color) {
+                        mask |= 1 << (x - x1);
+                    }
+                });
+
+                while mask != 0 {
+                    self.flip(x1 + mask.trailing_zeros() as i32, y);
+                    mask &= mask - 1;
+                }
+            }
+        }
+    }
+
+    // Column packed images share the page layout of the buffer so
+    // they are copied a byte, 8 rows, at a time.
+    fn blit_mono(&mut self, image: &ImageData, bounds: &BoundingBox, area: &BoundingBox) {
+        let data = image.data();
+        let (width, height) = (image.width(), image.height());
+
+        for page in 0..self.chunk_height.div_ceil(8) {
+            let page_y = self.clip.y1 + (page * 8) as i32;
+
+            // Rows of this page inside both the image and the chunk.
+            let top = max(area.y1 - page_y, 0);
+            let bottom = min(area.y2 - page_y, 8);
+            if top >= bottom {
+                continue;
+            }
+            let rows = ((0xFFu16 << top) & (0xFFu16 >> (8 - bottom))) as u8;
+
+            let image_y = page_y as i64 - bounds.y1 as i64;
+            let row = page * self.chunk_width;
+
+            for x in area.x1..area.x2 {
+                let image_x = (x - bounds.x1) as u32;
+                let bits = image::mono_column(data, width, height, image_x, image_y);
+
+                let opaque = match image.transparency() {
+                    Transparency::Opaque => 0xFF,
+                    Transparency::Mask(mask) => image::mono_column(mask, width, height, image_x, image_y),
+                    Transparency::Key(Rgb { r: 0, g: 0, b: 0 }) => bits,
+                    Transparency::Key(Rgb { r: 255, g: 255, b: 255 }) => !bits,
+                    Transparency::Key(_) => 0xFF,
+                };
+
+                let mask = rows & opaque;
+                let byte = &mut self.buffer[(row + (x - self.clip.x1) as u32) as usize];
+                *byte = (*byte & !mask) | (bits & mask);
+            }
         }
     }
 
@@ -80,16 +193,16 @@ where
 
         for x in (0..self.width).step_by(self.chunk_width as usize) {
             for y in (0..self.height).step_by(self.chunk_height as usize) {
-                self.set_chunk(x, y)?;
-                self.clear()?;
-                self.flush().await?;
+                <Self as Renderer>::set_chunk(self, x, y)?;
+                <Self as Renderer>::clear(self)?;
+                <Self as Renderer>::flush(self).await?;
             }
         }
         Ok(())
     }
 }
 
-impl<'a, DI, const BUFFER_SIZE: usize> Renderer for Sh1107Render<'a, DI, BUFFER_SIZE> 
+impl<'a, DI, const BUFFER_SIZE: usize, C: Color> Renderer<C> for Sh1107Render<'a, DI, BUFFER_SIZE> 
 where
     DI: AsyncWriteOnlyDataCommand,
 {
@@ -111,52 +224,89 @@ where
             return Err(RendererError::InvalidChunkOffset{x, y})
         }
 
-        self.clip = BoundingBox{
-            x1: x,
-            y1: y,
-            x2: x + self.chunk_width,
-            y2: y + self.chunk_height,
-        };
+        let (x, y) = (x as i32, y as i32);
+        self.clip = BoundingBox::new(x, y, x + self.chunk_width as i32, y + self.chunk_height as i32)
+            .intersection(&BoundingBox::new(0, 0, self.width as i32, self.height as i32));
 
         Ok(())
     }
 
-    fn draw(&mut self, command: &Command) -> Result<(), RendererError> {
+    fn draw(&mut self, command: &Command<C>) -> Result<(), RendererError> {
 
         use CommandType::*;
         let clip = self.clip;
+        let mut quantizer = Quantizer::new(self.mono, &clip);
+
         match command.flavor {
             Null => Ok(()),
-            Rect(rgb) => {
+            Rect(paint) => {
+                let area = command.bounds.intersection(&clip);
 
-                let color = rgb.r|rgb.g|rgb.b;
+                if area.is_empty() {
+                    return Ok(());
+                }
+
+                for y in area.y1..area.y2 {
+                    self.fill_span(&mut quantizer, Span { y, x1: area.x1, x2: area.x2 }, paint);
+                }
+
+                Ok(())
+            }
+            StyledRect { .. } | Circle(_) | Ellipse(_) | Line { .. } | Polyline { .. }
+            | QuadraticBezier { .. } | CubicBezier { .. } | Triangle { .. } | Polygon { .. }
+            | Arc { .. } | Sector { .. } => {
+                command.spans(&clip, |span, paint| self.fill_span(&mut quantizer, span, paint));
+
+                Ok(())
+            }
+            Text { text, font, color } => {
+                // Dithered text is unreadable so it is either on or off.
+                let paint = if quantizer.solid_on(color) {
+                    Paint::Solid(Rgb { r: 255, g: 255, b: 255 })
+                } else {
+                    Paint::Solid(Rgb { r: 0, g: 0, b: 0 })
+                };
+
+                let glyph_width = font.0.character_size.width;
+                let advance = glyph_width + font.0.character_spacing;
 
-                let x1 = max(command.bounds.x1, clip.x1);
                 let y1 = max(command.bounds.y1, clip.y1);
-                let x2 = min(command.bounds.x2, clip.x2);
                 let y2 = min(command.bounds.y2, clip.y2);
 
-                // Offset from chunk left
-                let x = x1 - clip.x1;
-                let x_end = x2 - clip.x1;
-                // Offset fron chunk top
-                let y = y1 - clip.y1;
-                let y_end = y2 - clip.y1;
-
-                // Each byte is a 8 pixel high column with the fist chunk_width bytes
-                // being row 0-7 and each consecutive chunk_width bytes being the 
-                // next 8 row. 
-                for x_i in x..x_end {
-                    for y_i in y..y_end {
-                        let index = x_i * (y_i/8 + 1);
-                        let byte = &mut self.buffer[index as usize];
-                        let bit = y_i % 8;
-
-                        let set_bit = 1u8<<bit;
-                        if color > 0 {
-                            *byte |= set_bit;
-                        } else {
-                            *byte &= !set_bit;
+                for (i, c) in text.chars().enumerate() {
+                    let glyph_x = command.bounds.x1 + (i as u32 * advance) as i32;
+                    let x1 = max(glyph_x, clip.x1);
+                    let x2 = min(glyph_x + glyph_width as i32, clip.x2);
+
+                    for y in y1..y2 {
+                        for x in x1..x2 {
+                            let on = font.glyph_pixel(c, (x - glyph_x) as u32, (y - command.bounds.y1) as u32);
+                            if on {
+                                self.fill_span(&mut quantizer, Span { y, x1: x, x2: x + 1 }, paint);
+                            }
+                        }
+                    }
+                }
+
+                Ok(())
+            }
+            Image(image) => {
+                let bounds = command.bounds;
+                let area = bounds.intersection(&clip);
+
+                if area.is_empty() {
+                    return Ok(());
+                }
+
+                if image.format() == ImageFormat::Mono {
+                    self.blit_mono(image, &bounds, &area);
+                    return Ok(());
+                }
+
+                for y in area.y1..area.y2 {
+                    for x in area.x1..area.x2 {
+                        if let Some(rgb) = image.pixel((x - bounds.x1) as u32, (y - bounds.y1) as u32) {
+                            self.fill_span(&mut quantizer, Span { y, x1: x, x2: x + 1 }, Paint::Solid(rgb));
                         }
                     }
                 }
@@ -166,6 +316,30 @@ where
         }
     }
 
+    fn draw_blended(&mut self, command: &Command<C>, below: Below<'_, C>) -> Result<(), RendererError> {
+        let clip = self.clip;
+        let mut quantizer = Quantizer::new(self.mono, &clip);
+
+        // What is beneath is already in the buffer, so inverting is
+        // just flipping bits.
+        match (command.blend, command.opacity) {
+            (BlendMode::Invert, 255) => self.flip_once(command, |_| true),
+            (BlendMode::Xor, 255) => self.flip_once(command, |color| quantizer.solid_on(color)),
+            (blend, opacity) => {
+                let mono = self.mono;
+                let mix = |blend: BlendMode, opacity, src, dst| {
+                    blend.blend_bits(opacity, src, dst, |color| mono.solid_on(color))
+                };
+                blend::composite(command, below, &clip, mix, |x, y, src, dst| {
+                    let paint = Paint::Solid(blend.blend(opacity, src, dst));
+                    self.fill_span(&mut quantizer, Span { y, x1: x, x2: x + 1 }, paint)
+                })
+            }
+        }
+
+        Ok(())
+    }
+
     fn clear(&mut self) -> Result<(), RendererError> {
         self.buffer = [0x00u8;BUFFER_SIZE];
         Ok(())
@@ -174,9 +348,11 @@ where
     
     async fn flush(&mut self) -> Result<(), RendererError> {
 
+        // Chunks on the right and bottom edges can be cut short by the
+        // display, and only what is on it is sent.
         let column_start = self.clip.x1 as u8;
         let row_start = (self.clip.y1/8) as u8;
-        let row_end = row_start + (self.chunk_height as u8 /8);
+        let row_end = row_start + self.clip.height().div_ceil(8) as u8;
 
         for (index,row) in (row_start..row_end).enumerate() {
             i2c::Command::PageAddress(row)
@@ -190,7 +366,7 @@ where
             .await?;
 
             let start = self.chunk_width as usize * index;
-            let end = start + self.chunk_width as usize;
+            let end = start + self.clip.width() as usize;
             let data: DataFormat<'_> = DataFormat::U8(&self.buffer[start..end]);
             self.display.send_data(data).await?;
         }
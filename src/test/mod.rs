@@ -1,14 +1,205 @@
 use super::*;
+use super::Point;
 
 use smol;
 
 use embedded_graphics::{
     pixelcolor::Rgb888,
     prelude::*,
+    prelude::Point as EgPoint,
 };
 
 use embedded_graphics_simulator::{SimulatorDisplay, Window, OutputSettingsBuilder};
 
+use display_interface::{AsyncWriteOnlyDataCommand, DataFormat, DisplayError};
+
+const MOCK_SIZE: usize = 64;
+
+/// Records what `Sh1107Render` sends so tests can look at the pixels
+/// that would be on the panel.
+struct MockSh1107 {
+    page: usize,
+    column: usize,
+    pages: [[u8; MOCK_SIZE]; MOCK_SIZE / 8],
+    /// Fail the write after this many more, like a NAK on the bus.
+    nak_after: Option<usize>,
+    /// Data bytes sent so far.
+    sent: usize,
+}
+
+impl MockSh1107 {
+    fn new() -> Self {
+        MockSh1107 {
+            page: 0,
+            column: 0,
+            pages: [[0u8; MOCK_SIZE]; MOCK_SIZE / 8],
+            nak_after: None,
+            sent: 0,
+        }
+    }
+
+    fn get_pixel(&self, x: usize, y: usize) -> bool {
+        self.pages[y / 8][x] & (1 << (y % 8)) != 0
+    }
+}
+
+impl AsyncWriteOnlyDataCommand for MockSh1107 {
+    async fn send_commands(&mut self, cmd: DataFormat<'_>) -> Result<(), DisplayError> {
+        if let DataFormat::U8(&[byte]) = cmd {
+            match byte & 0xF0 {
+                0xB0 => self.page = (byte & 0x0F) as usize,
+                0x00 => self.column = (self.column & 0xF0) | (byte & 0x0F) as usize,
+                0x10 => self.column = (self.column & 0x0F) | ((byte & 0x0F) as usize) << 4,
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    async fn send_data(&mut self, buf: DataFormat<'_>) -> Result<(), DisplayError> {
+        match self.nak_after {
+            Some(0) => {
+                self.nak_after = None;
+                return Err(DisplayError::BusWriteError);
+            }
+            Some(writes) => self.nak_after = Some(writes - 1),
+            None => (),
+        }
+
+        if let DataFormat::U8(data) = buf {
+            for byte in data {
+                self.pages[self.page][self.column] = *byte;
+                self.column += 1;
+            }
+            self.sent += data.len();
+        }
+        Ok(())
+    }
+}
+
+/// Counts the tiles the display list paints without drawing anything.
+struct CountingRenderer {
+    width: u32,
+    height: u32,
+    chunk: u32,
+    flushes: u32,
+    clears: u32,
+    draws: u32,
+    /// Every call made, including the ones that only ask about size.
+    calls: core::cell::Cell<u32>,
+    /// Fail the flush after this many more.
+    fail_after: Option<u32>,
+}
+
+impl CountingRenderer {
+    fn new(width: u32, height: u32, chunk: u32) -> Self {
+        CountingRenderer { width, height, chunk, flushes: 0, clears: 0, draws: 0, calls: Default::default(), fail_after: None }
+    }
+}
+
+impl Renderer for CountingRenderer {
+    fn width(&self) -> u32 {
+        self.calls.set(self.calls.get() + 1);
+        self.width
+    }
+
+    fn height(&self) -> u32 {
+        self.calls.set(self.calls.get() + 1);
+        self.height
+    }
+
+    fn chunk_size(&self) -> (u32, u32) {
+        self.calls.set(self.calls.get() + 1);
+        (self.chunk, self.chunk)
+    }
+
+    fn set_chunk(&mut self, _x: u32, _y: u32) -> Result<(), RendererError> {
+        self.calls.set(self.calls.get() + 1);
+        Ok(())
+    }
+
+    fn clear(&mut self) -> Result<(), RendererError> {
+        self.calls.set(self.calls.get() + 1);
+        self.clears += 1;
+        Ok(())
+    }
+
+    fn draw(&mut self, _command: &Command) -> Result<(), RendererError> {
+        self.calls.set(self.calls.get() + 1);
+        self.draws += 1;
+        Ok(())
+    }
+
+    fn draw_blended(&mut self, _command: &Command, _below: Below) -> Result<(), RendererError> {
+        self.calls.set(self.calls.get() + 1);
+        self.draws += 1;
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> Result<(), RendererError> {
+        self.calls.set(self.calls.get() + 1);
+        match self.fail_after {
+            Some(0) => {
+                self.fail_after = None;
+                return Err(RendererError::BackingError);
+            }
+            Some(flushes) => self.fail_after = Some(flushes - 1),
+            None => (),
+        }
+        self.flushes += 1;
+        Ok(())
+    }
+}
+
+/// Draw `commands` with both renderers on a 64x64 panel and check they
+/// agree pixel for pixel.
+fn assert_renderers_agree<const LENGTH: usize, C: Color>(commands: &[Command<C>; LENGTH]) -> Result<(), DisplayListError>
+where
+    embedded_graphics::pixelcolor::BinaryColor: From<C>,
+{
+    assert_renderers_agree_at(MOCK_SIZE as i32, MOCK_SIZE as i32, commands)?;
+    Ok(())
+}
+
+/// Draw `commands` with both renderers on a `width` by `height` panel
+/// and check they agree pixel for pixel. Returns what the SH1107 drew
+/// for tests to look at further.
+fn assert_renderers_agree_at<const LENGTH: usize, C: Color>(
+    width: i32,
+    height: i32,
+    commands: &[Command<C>; LENGTH],
+) -> Result<MockSh1107, DisplayListError>
+where
+    embedded_graphics::pixelcolor::BinaryColor: From<C>,
+{
+    use embedded_graphics::pixelcolor::BinaryColor;
+
+    let mut display = SimulatorDisplay::<BinaryColor>::new(Size::new(width as u32, height as u32));
+    let mut renderer = embedded_render::EmbeddedRender::new(&mut display, 16, 16);
+    let mut list = DisplayList::<LENGTH, C>::new();
+    for (i, command) in commands.iter().enumerate() {
+        list.set(i, *command)?;
+    }
+    smol::block_on(list.draw(&mut renderer))?;
+
+    let mut mock = MockSh1107::new();
+    let mut sh1107 = sh1107_render::Sh1107Render::<_, 32>::new(&mut mock, width as u32, height as u32, 16, 16);
+    let mut list = DisplayList::<LENGTH, C>::new();
+    for (i, command) in commands.iter().enumerate() {
+        list.set(i, *command)?;
+    }
+    smol::block_on(list.draw(&mut sh1107))?;
+
+    for x in 0..width {
+        for y in 0..height {
+            let expected = display.get_pixel(EgPoint::new(x, y)) == BinaryColor::On;
+            assert_eq!(mock.get_pixel(x as usize, y as usize), expected, "pixel {x}, {y}");
+        }
+    }
+
+    Ok(mock)
+}
+
 
 fn test2() -> Result<(), DisplayListError> {
     let mut display = SimulatorDisplay::<Rgb888>::new(Size::new(320, 240));
@@ -19,21 +210,21 @@ fn test2() -> Result<(), DisplayListError> {
 
 
     let bounds = BoundingBox::new( 176, 16, 192, 176);
-    let rgb = Rgb::new( 0, 64, 128 );
+    let rgb = Rgb::from_rgb( 0, 64, 128 );
 
     let rect = Command::new_rect(bounds, rgb);
 
     commands.set(0, rect)?;
 
     let bounds = BoundingBox::new( 208, 16, 224, 176);
-    let rgb = Rgb::new( 0, 128, 64 );
+    let rgb = Rgb::from_rgb( 0, 128, 64 );
 
     let rect = Command::new_rect(bounds, rgb);
 
     commands.set(2, rect)?;
 
     let mut bounds = BoundingBox::new( 64, 32, 160, 128);
-    let mut rgb = Rgb::new( 64, 64, 64 );
+    let mut rgb = Rgb::from_rgb( 64, 64, 64 );
 
     let rect = Command::new_rect(bounds.clone(), rgb.clone());
 
@@ -72,4 +263,1410 @@ fn it_works2() {
     let result = test2();
 
     assert_eq!(result, Ok(()));
-}
\ No newline at end of file
+}
+#[test]
+fn circle_coverage_is_exact() {
+    let style = ShapeStyle::fill(Rgb::from_rgb(255, 255, 255));
+    let circle = Command::new_circle(Point::new(0, 0), 64, style);
+
+    // The middle of the circle is fully painted.
+    let center = BoundingBox::new(24, 24, 40, 40);
+    assert_eq!(circle.covers(&center), Ok(true));
+    assert_eq!(circle.intersects(&center), Ok(true));
+
+    // The corner of the bounding box is not painted at all.
+    let corner = BoundingBox::new(0, 0, 8, 8);
+    assert_eq!(circle.covers(&corner), Ok(false));
+    assert_eq!(circle.intersects(&corner), Ok(false));
+
+    // The edge of the circle touches but does not cover.
+    let edge = BoundingBox::new(0, 24, 8, 40);
+    assert_eq!(circle.covers(&edge), Ok(false));
+    assert_eq!(circle.intersects(&edge), Ok(true));
+
+    // A ring never covers its middle.
+    let ring = Command::new_circle(
+        Point::new(0, 0),
+        64,
+        ShapeStyle::stroke(Rgb::from_rgb(255, 255, 255), 4),
+    );
+    assert_eq!(ring.covers(&center), Ok(false));
+    assert_eq!(ring.intersects(&center), Ok(false));
+    assert_eq!(ring.intersects(&edge), Ok(true));
+}
+
+#[test]
+fn ellipse_draws_what_it_intersects() -> Result<(), DisplayListError> {
+    use embedded_graphics::pixelcolor::BinaryColor;
+
+    let mut display = SimulatorDisplay::<BinaryColor>::new(Size::new(64, 32));
+    let mut renderer = embedded_render::EmbeddedRender::new(&mut display, 8, 8);
+    let mut commands = DisplayList::<1>::new();
+
+    let ellipse = Command::new_ellipse(
+        BoundingBox::new(0, 0, 64, 32),
+        ShapeStyle::fill_and_stroke(Rgb::from_rgb(255, 255, 255), Rgb::from_rgb(200, 200, 200), 2),
+    );
+    commands.set(0, ellipse)?;
+
+    smol::block_on(commands.draw(&mut renderer))?;
+
+    let display = renderer.get_display();
+    for x in (0..64).step_by(8) {
+        for y in (0..32).step_by(8) {
+            let tile = BoundingBox::new(x, y, x + 8, y + 8);
+            let mut lit = 0;
+            for px in x..x + 8 {
+                for py in y..y + 8 {
+                    let point = EgPoint::new(px, py);
+                    if display.get_pixel(point) == BinaryColor::On {
+                        lit += 1;
+                    }
+                }
+            }
+
+            assert_eq!(ellipse.intersects(&tile)?, lit > 0);
+            assert_eq!(ellipse.covers(&tile)?, lit == 64);
+        }
+    }
+
+    // Pixel centers on the axes are inside, the corners are not.
+    assert_eq!(display.get_pixel(EgPoint::new(0, 16)), BinaryColor::On);
+    assert_eq!(display.get_pixel(EgPoint::new(0, 0)), BinaryColor::Off);
+    assert_eq!(display.get_pixel(EgPoint::new(63, 31)), BinaryColor::Off);
+
+    Ok(())
+}
+
+#[test]
+fn diagonal_line_only_touches_tiles_on_its_path() -> Result<(), DisplayListError> {
+    use embedded_graphics::pixelcolor::BinaryColor;
+
+    let mut display = SimulatorDisplay::<BinaryColor>::new(Size::new(64, 64));
+    let mut renderer = embedded_render::EmbeddedRender::new(&mut display, 8, 8);
+    let mut commands = DisplayList::<2>::new();
+
+    let white = Rgb::from_rgb(255, 255, 255);
+    let line = Command::new_line(Point::new(2, 2), Point::new(61, 61), 3, white);
+    let zigzag = Command::new_polyline(
+        &[Point::new(0, 40), Point::new(20, 60), Point::new(40, 40)],
+        1,
+        white,
+    )?;
+    commands.set(0, line)?;
+    commands.set(1, zigzag)?;
+
+    smol::block_on(commands.draw(&mut renderer))?;
+
+    let display = renderer.get_display();
+    let mut touched = 0;
+    for x in (0..64).step_by(8) {
+        for y in (0..64).step_by(8) {
+            let tile = BoundingBox::new(x, y, x + 8, y + 8);
+            let mut lit = false;
+            for px in x..x + 8 {
+                for py in y..y + 8 {
+                    let point = EgPoint::new(px, py);
+                    lit |= display.get_pixel(point) == BinaryColor::On;
+                }
+            }
+
+            let intersects = line.intersects(&tile)? || zigzag.intersects(&tile)?;
+            assert_eq!(intersects, lit);
+            if line.intersects(&tile)? {
+                touched += 1;
+            }
+        }
+    }
+
+    // The diagonal passes through the 8 tiles on the diagonal and
+    // clips the corners of the neighbouring ones, not all 64.
+    assert!(touched < 24);
+    assert!(!line.covers(&BoundingBox::new(0, 0, 8, 8))?);
+
+    let too_many = [Point::new(0, 0); MAX_POINTS + 1];
+    assert_eq!(
+        Command::new_polyline(&too_many, 1, white),
+        Err(CommandError::TooManyPoints)
+    );
+    assert_eq!(
+        Command::new_polyline(&[Point::new(0, 0), Point::new(40_000, 0)], 1, white),
+        Err(CommandError::PointOutOfRange)
+    );
+
+    // Points computed at runtime are copied into the command, so the
+    // buffer they came from can go away before the command is drawn.
+    let graph = {
+        let mut samples = [Point::new(0, 0); MAX_POINTS];
+        for (i, sample) in samples.iter_mut().enumerate() {
+            *sample = Point::new(i as i32 * 8, if i % 2 == 0 { 60 } else { 50 });
+        }
+        Command::new_polyline(&samples, 1, white)?
+    };
+    if let CommandType::Polyline { points, .. } = graph.flavor {
+        assert_eq!(points.len(), MAX_POINTS);
+        assert_eq!(points.get(3), Some(Point::new(24, 50)));
+        assert_eq!(points.get(MAX_POINTS), None);
+    } else {
+        panic!("not a polyline");
+    }
+    commands.set(1, graph)?;
+    smol::block_on(commands.draw(&mut renderer))?;
+    assert_eq!(renderer.get_display().get_pixel(EgPoint::new(24, 50)), BinaryColor::On);
+
+    Ok(())
+}
+
+#[test]
+fn text_bounds_follow_font_metrics() -> Result<(), DisplayListError> {
+    use embedded_graphics::mono_font::ascii::FONT_6X10;
+
+    let white = Rgb::from_rgb(255, 255, 255);
+    let short = Command::new_text(Point::new(2, 3), "Hi", &FONT_6X10, white)?;
+    let long = Command::new_text(Point::new(2, 3), "Hello", &FONT_6X10, white)?;
+
+    assert_eq!(short.bounds, BoundingBox::new(2, 3, 2 + 12, 3 + 10));
+    assert_eq!(long.bounds, BoundingBox::new(2, 3, 2 + 30, 3 + 10));
+
+    // The text is borrowed so its length isn't limited.
+    let banner = Command::new_text(Point::new(0, 0), "Battery low, connect the charger", &FONT_6X10, white)?;
+    assert_eq!(banner.bounds, BoundingBox::new(0, 0, 32 * 6, 10));
+
+    // A line break would draw below the bounds, so it is refused.
+    assert_eq!(
+        Command::new_text(Point::new(0, 0), "A\nB", &FONT_6X10, white),
+        Err(CommandError::ControlCharacter)
+    );
+
+    // Straddle the chunk seams so the glyph blitter has to clip.
+    let label = Command::new_text(Point::new(9, 13), "Volt 3.3", &FONT_6X10, white)?;
+    let circle = Command::new_circle(Point::new(30, 20), 30, ShapeStyle::stroke(white, 3));
+    let line = Command::new_line(Point::new(0, 63), Point::new(63, 30), 2, white);
+
+    assert_renderers_agree(&[circle, line, label])
+}
+
+// A 12x12 ring, column packed.
+static RING: [u8; 24] = [
+    0xF0, 0xFC, 0x0E, 0x06, 0x03, 0x03, 0x03, 0x03, 0x06, 0x0E, 0xFC, 0xF0,
+    0x00, 0x03, 0x07, 0x06, 0x0C, 0x0C, 0x0C, 0x0C, 0x06, 0x07, 0x03, 0x00,
+];
+
+// Only the left half of the ring is drawn.
+static LEFT_HALF: [u8; 24] = [
+    0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x0F, 0x0F, 0x0F, 0x0F, 0x0F, 0x0F, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+// A 4x3 gradient with a black key in the corners.
+static GRAY: [u8; 12] = [
+    0, 80, 80, 0,
+    40, 120, 160, 200,
+    0, 255, 255, 0,
+];
+
+// A 2x2 red, green, blue and black swatch.
+static SWATCH: [u8; 8] = [0xF8, 0x00, 0x07, 0xE0, 0x00, 0x1F, 0x00, 0x00];
+
+/// Unwrap image data that is known to be long enough, which `?` can't
+/// do in a `static`.
+const fn image(image: Result<ImageData, CommandError>) -> ImageData {
+    match image {
+        Ok(image) => image,
+        Err(_) => panic!("image data too short"),
+    }
+}
+
+static RING_IMAGE: ImageData = image(ImageData::new(&RING, ImageFormat::Mono, 12, 12));
+static KEYED_RING: ImageData = image(ImageData::new(&RING, ImageFormat::Mono, 12, 12)).with_key(Rgb::BLACK);
+static MASKED_RING: ImageData = image(image(ImageData::new(&RING, ImageFormat::Mono, 12, 12)).with_mask(&LEFT_HALF));
+static GRAY_IMAGE: ImageData = image(ImageData::new(&GRAY, ImageFormat::Gray8, 4, 3)).with_key(Rgb::BLACK);
+static SWATCH_IMAGE: ImageData = image(ImageData::new(&SWATCH, ImageFormat::Rgb565, 2, 2));
+
+#[test]
+fn images_blit_clipped_in_both_renderers() -> Result<(), DisplayListError> {
+    let (ring, gray, swatch) = (&RING_IMAGE, &GRAY_IMAGE, &SWATCH_IMAGE);
+
+    assert_eq!(ring.pixel(0, 4), Some(Rgb::from_rgb(255, 255, 255)));
+    assert_eq!(ring.pixel(5, 5), Some(Rgb::from_rgb(0, 0, 0)));
+    assert_eq!(gray.pixel(0, 0), None);
+    assert_eq!(gray.pixel(1, 1), Some(Rgb::from_rgb(120, 120, 120)));
+    assert_eq!(swatch.pixel(0, 0), Some(Rgb { r: 255, g: 0, b: 0 }));
+    assert_eq!(swatch.pixel(1, 0), Some(Rgb { r: 0, g: 255, b: 0 }));
+    assert_eq!(swatch.pixel(0, 1), Some(Rgb { r: 0, g: 0, b: 255 }));
+
+    assert_eq!(
+        ImageData::new(&GRAY, ImageFormat::Gray8, 4, 4),
+        Err(CommandError::ImageDataTooShort)
+    );
+
+    // Opaque images cover their bounds, keyed ones don't.
+    let opaque: Command = Command::new_image(Point::new(0, 0), ring);
+    let keyed: Command = Command::new_image(Point::new(0, 0), &KEYED_RING);
+    let tile = BoundingBox::new(2, 2, 10, 10);
+    assert_eq!(opaque.covers(&tile), Ok(true));
+    assert_eq!(keyed.covers(&tile), Ok(false));
+
+    let backdrop = Command::new_rect(BoundingBox::new(0, 0, 64, 64), Rgb::from_rgb(255, 255, 255));
+    let black = Command::new_rect(BoundingBox::new(24, 0, 64, 40), Rgb::from_rgb(0, 0, 0));
+
+    // Unaligned to the pages and chunks so the column packed path
+    // has to shift and mask.
+    let commands = [
+        backdrop,
+        black,
+        Command::new_image(Point::new(11, 13), ring),
+        Command::new_image(Point::new(27, 5), &KEYED_RING),
+        Command::new_image(Point::new(42, 29), &MASKED_RING),
+        Command::new_image(Point::new(14, 46), gray),
+        Command::new_image(Point::new(31, 15), swatch),
+    ];
+
+    assert_renderers_agree(&commands)
+}
+
+#[test]
+fn hollow_and_rounded_rects_do_not_occlude() -> Result<(), DisplayListError> {
+    use embedded_graphics::pixelcolor::BinaryColor;
+
+    let white = Rgb::from_rgb(255, 255, 255);
+    let black = Rgb::from_rgb(0, 0, 0);
+    let bounds = BoundingBox::new(0, 0, 64, 64);
+
+    let outline = Command::new_styled_rect(bounds, ShapeStyle::stroke(white, 2), 0);
+    let rounded = Command::new_styled_rect(bounds, ShapeStyle::fill(white), 12);
+    let square = Command::new_styled_rect(bounds, ShapeStyle::fill(white), 0);
+
+    let middle = BoundingBox::new(16, 16, 32, 32);
+    let corner = BoundingBox::new(0, 0, 8, 8);
+    let edge = BoundingBox::new(0, 16, 16, 32);
+
+    assert_eq!(outline.covers(&middle), Ok(false));
+    assert_eq!(outline.intersects(&middle), Ok(false));
+    assert_eq!(outline.intersects(&corner), Ok(true));
+    assert_eq!(rounded.covers(&middle), Ok(true));
+    assert_eq!(rounded.covers(&corner), Ok(false));
+    assert_eq!(rounded.covers(&edge), Ok(true));
+    assert_eq!(square.covers(&corner), Ok(true));
+
+    // The rect underneath must still show through the hollow middle.
+    let mut display = SimulatorDisplay::<BinaryColor>::new(Size::new(64, 64));
+    let mut renderer = embedded_render::EmbeddedRender::new(&mut display, 16, 16);
+    let mut commands = DisplayList::<2>::new();
+    commands.set(0, Command::new_rect(middle, white))?;
+    commands.set(1, outline)?;
+    smol::block_on(commands.draw(&mut renderer))?;
+
+    let display = renderer.get_display();
+    assert_eq!(display.get_pixel(EgPoint::new(20, 20)), BinaryColor::On);
+    assert_eq!(display.get_pixel(EgPoint::new(40, 40)), BinaryColor::Off);
+    assert_eq!(display.get_pixel(EgPoint::new(1, 40)), BinaryColor::On);
+
+    let button = Command::new_styled_rect(
+        BoundingBox::new(5, 9, 50, 30),
+        ShapeStyle::fill_and_stroke(black, white, 3),
+        7,
+    );
+    let pill = Command::new_styled_rect(
+        BoundingBox::new(20, 36, 60, 52),
+        ShapeStyle::stroke(white, 1),
+        100,
+    );
+
+    assert_renderers_agree(&[button, pill])
+}
+
+#[test]
+fn polygons_only_touch_tiles_they_paint() -> Result<(), DisplayListError> {
+    let white = Rgb::from_rgb(255, 255, 255);
+
+    // A polygon through the corners of a box paints the same pixels
+    // as a rect with that box.
+    let square = Command::new_polygon(
+        &[Point::new(3, 5), Point::new(13, 5), Point::new(13, 17), Point::new(3, 17)],
+        white,
+    )?;
+    let rect = Command::new_styled_rect(BoundingBox::new(3, 5, 13, 17), ShapeStyle::fill(white), 0);
+    let clip = BoundingBox::new(0, 0, 16, 24);
+    let (mut a, mut b) = ([(0, 0, 0); 16], [(0, 0, 0); 16]);
+    let (mut i, mut j) = (0, 0);
+    square.spans(&clip, |span, _| { a[i] = (span.y, span.x1, span.x2); i += 1; });
+    rect.spans(&clip, |span, _| { b[j] = (span.y, span.x1, span.x2); j += 1; });
+    assert_eq!((i, a), (j, b));
+
+    // A thin needle from corner to corner only touches the tiles on
+    // the diagonal and their neighbours.
+    let needle = Command::new_triangle(Point::new(0, 0), Point::new(64, 62), Point::new(62, 64), white);
+    let mut touched = 0;
+    for x in (0..64).step_by(8) {
+        for y in (0..64).step_by(8) {
+            if needle.intersects(&BoundingBox::new(x, y, x + 8, y + 8))? {
+                touched += 1;
+            }
+        }
+    }
+    assert!(touched < 24, "{touched}");
+
+    let play = Command::new_triangle(Point::new(16, 16), Point::new(48, 32), Point::new(16, 48), white);
+    assert_eq!(play.covers(&BoundingBox::new(16, 24, 24, 40)), Ok(true));
+    assert_eq!(play.covers(&BoundingBox::new(40, 16, 48, 24)), Ok(false));
+    assert_eq!(play.intersects(&BoundingBox::new(40, 16, 48, 24)), Ok(false));
+
+    // A concave arrow straddling chunk seams, moved into place at
+    // runtime the way a gauge needle would be each frame.
+    let arrow_at = |dx: i32| {
+        let mut points = [
+            Point::new(5, 40),
+            Point::new(30, 40),
+            Point::new(30, 33),
+            Point::new(45, 47),
+            Point::new(30, 61),
+            Point::new(30, 54),
+            Point::new(5, 54),
+        ];
+        for point in points.iter_mut() {
+            point.x += dx;
+        }
+        Command::new_polygon(&points, white)
+    };
+    let arrow = arrow_at(0)?;
+    let moved = arrow_at(3)?.bounds;
+    assert_eq!((moved.x1, moved.x2), (arrow.bounds.x1 + 3, arrow.bounds.x2 + 3));
+
+    assert_renderers_agree(&[needle, play, arrow])
+}
+
+#[test]
+fn growing_arc_only_dirties_the_tiles_it_changes() -> Result<(), DisplayListError> {
+    let white = Rgb::from_rgb(255, 255, 255);
+    let center = Point::new(32, 32);
+
+    // A full sweep is the whole ring and a sector is a quarter disc.
+    let ring = Command::new_arc(center, 30, 0, 360, 4, white);
+    assert_eq!(ring.intersects(&BoundingBox::new(24, 24, 40, 40)), Ok(false));
+    assert_eq!(ring.intersects(&BoundingBox::new(0, 24, 8, 40)), Ok(true));
+
+    let quarter = Command::new_sector(center, 30, 0, 90, white);
+    assert_eq!(quarter.covers(&BoundingBox::new(36, 36, 44, 44)), Ok(true));
+    assert_eq!(quarter.intersects(&BoundingBox::new(16, 36, 24, 44)), Ok(false));
+    assert_eq!(quarter.intersects(&BoundingBox::new(36, 16, 44, 24)), Ok(false));
+
+    // Negative sweeps go anticlockwise.
+    let back = Command::new_sector(center, 30, 90, -90, white);
+    for y in 24..40 {
+        let row = BoundingBox::new(0, y, 64, y + 1);
+        assert!(RowSpans::of(&back, &row) == RowSpans::of(&quarter, &row));
+    }
+
+    let mut renderer = CountingRenderer::new(64, 64, 8);
+    let mut commands = DisplayList::<1>::new();
+
+    commands.set(0, Command::new_arc(center, 30, -90, 90, 4, white))?;
+    smol::block_on(commands.draw(&mut renderer))?;
+    let first = renderer.flushes;
+
+    renderer.flushes = 0;
+    commands.update(0, Command::new_arc(center, 30, -90, 100, 4, white))?;
+    smol::block_on(commands.draw(&mut renderer))?;
+
+    // Only the tiles around the end of the arc are repainted.
+    assert!(renderer.flushes > 0);
+    assert!(renderer.flushes <= 3, "{} of {first}", renderer.flushes);
+
+    let gauge = Command::new_arc(Point::new(20, 40), 18, 135, 270, 3, white);
+    let needle = Command::new_sector(Point::new(20, 40), 14, 200, 25, white);
+    let pie = Command::new_sector(Point::new(48, 16), 13, -30, 300, white);
+
+    assert_renderers_agree(&[gauge, needle, pie])
+}
+
+#[test]
+fn bezier_bounds_follow_the_curve() -> Result<(), DisplayListError> {
+    let white = Rgb::from_rgb(255, 255, 255);
+
+    // The control point is at the top of the screen but the curve
+    // only reaches half way up.
+    let arch = Command::new_quadratic_bezier(
+        Point::new(2, 60),
+        Point::new(32, 0),
+        Point::new(62, 60),
+        1,
+        white,
+    );
+    assert_eq!(arch.bounds, BoundingBox::new(2, 30, 63, 61));
+    assert_eq!(arch.intersects(&BoundingBox::new(24, 0, 40, 16)), Ok(false));
+    assert_eq!(arch.intersects(&BoundingBox::new(24, 24, 40, 40)), Ok(true));
+
+    // An S curve whose control points lie well outside it.
+    let wave = Command::new_cubic_bezier(
+        Point::new(4, 32),
+        Point::new(24, 0),
+        Point::new(40, 63),
+        Point::new(60, 32),
+        2,
+        white,
+    );
+    assert!(wave.bounds.y1 > 8 && wave.bounds.y2 < 56, "{:?}", wave.bounds);
+
+    // The curve passes through its end points.
+    for (x, y) in [(4, 32), (60, 32)] {
+        assert_eq!(wave.intersects(&BoundingBox::new(x, y, x + 1, y + 1)), Ok(true));
+    }
+
+    assert_renderers_agree(&[arch, wave])?;
+
+    // Joins and curve segments overlap, and an inverting stroke must
+    // still flip each pixel it paints once.
+    static ZIGZAG: [Point; 4] = [Point::new(2, 50), Point::new(20, 10), Point::new(40, 54), Point::new(60, 12)];
+    let half = Command::new_rect(BoundingBox::new(0, 32, 64, 64), white);
+    let zigzag = Command::new_polyline(&ZIGZAG, 3, white)?.with_blend(BlendMode::Invert);
+    assert_renderers_agree(&[
+        half,
+        arch.with_blend(BlendMode::Invert),
+        wave.with_blend(BlendMode::Xor),
+        zigzag,
+    ])
+}
+
+#[test]
+fn gradients_shade_color_and_mono_panels() -> Result<(), DisplayListError> {
+    let black = Rgb::from_rgb(0, 0, 0);
+    let white = Rgb::from_rgb(255, 255, 255);
+
+    let across = Paint::LinearGradient {
+        start: ShortPoint::new(0, 0),
+        end: ShortPoint::new(64, 0),
+        from: black,
+        to: white,
+    };
+    assert_eq!(across.color_at(0, 10), black);
+    assert_eq!(across.color_at(32, 40), Rgb::from_rgb(127, 127, 127));
+    assert_eq!(across.color_at(70, 0), white);
+
+    let glow = Paint::RadialGradient {
+        center: ShortPoint::new(10, 10),
+        radius: 10,
+        from: white,
+        to: black,
+    };
+    assert_eq!(glow.color_at(10, 10), white);
+    assert_eq!(glow.color_at(15, 10), Rgb::from_rgb(128, 128, 128));
+    assert_eq!(glow.color_at(40, 40), black);
+
+    // Color targets get the gradient per pixel.
+    let mut display = SimulatorDisplay::<Rgb888>::new(Size::new(64, 16));
+    let mut renderer = embedded_render::EmbeddedRender::new(&mut display, 16, 16);
+    let mut commands = DisplayList::<1>::new();
+    commands.set(0, Command::new_rect(BoundingBox::new(0, 0, 64, 16), across))?;
+    smol::block_on(commands.draw(&mut renderer))?;
+
+    let display = renderer.get_display();
+    assert_eq!(display.get_pixel(EgPoint::new(0, 5)), Rgb888::new(0, 0, 0));
+    assert_eq!(display.get_pixel(EgPoint::new(32, 5)), Rgb888::new(127, 127, 127));
+
+    // A one bit panel dithers it, so more pixels are lit further along.
+    let mut mock = MockSh1107::new();
+    let mut sh1107 = sh1107_render::Sh1107Render::<_, 32>::new(&mut mock, 64, 64, 16, 16);
+    let mut commands = DisplayList::<1>::new();
+    let style = ShapeStyle::fill(across);
+    commands.set(0, Command::new_styled_rect(BoundingBox::new(0, 0, 64, 64), style, 0))?;
+    smol::block_on(commands.draw(&mut sh1107))?;
+
+    let lit = |x1: usize| {
+        (x1..x1 + 16)
+            .flat_map(|x| (0..64).map(move |y| (x, y)))
+            .filter(|&(x, y)| mock.get_pixel(x, y))
+            .count()
+    };
+    let quarters = [lit(0), lit(16), lit(32), lit(48)];
+    assert!(quarters.windows(2).all(|pair| pair[0] < pair[1]), "{quarters:?}");
+    assert!(quarters[0] < 16 * 64 / 4 && quarters[3] > 16 * 64 * 3 / 4, "{quarters:?}");
+
+    Ok(())
+}
+
+#[test]
+fn mono_strategies_follow_luminance() -> Result<(), DisplayListError> {
+    use embedded_graphics::pixelcolor::BinaryColor;
+
+    let dark = Rgb::from_rgb(60, 60, 60);
+    let gray = Rgb::from_rgb(128, 128, 128);
+    let area = BoundingBox::new(0, 0, 64, 64);
+
+    let lit = |mono: MonoStrategy, chunk: u32, rgb: Rgb| -> Result<MockSh1107, DisplayListError> {
+        let mut mock = MockSh1107::new();
+        let mut sh1107 = sh1107_render::Sh1107Render::<_, 512>::new(&mut mock, 64, 64, chunk, chunk);
+        sh1107.set_mono_strategy(mono);
+        let mut commands = DisplayList::<1>::new();
+        commands.set(0, Command::new_rect(area, rgb))?;
+        smol::block_on(commands.draw(&mut sh1107))?;
+        Ok(mock)
+    };
+    let count = |mock: &MockSh1107| {
+        (0..64).flat_map(|x| (0..64).map(move |y| (x, y)))
+            .filter(|&(x, y)| mock.get_pixel(x, y))
+            .count()
+    };
+
+    // Every color but black is on unless a luminance threshold is
+    // asked for.
+    assert_eq!(count(&lit(MonoStrategy::default(), 16, dark)?), 64 * 64);
+    assert_eq!(count(&lit(MonoStrategy::Threshold(128), 16, dark)?), 0);
+    assert_eq!(count(&lit(MonoStrategy::Threshold(40), 16, dark)?), 64 * 64);
+    for rgb in [Rgb::RED, Rgb::GREEN, Rgb::BLUE, Rgb::from_rgb(0, 0, 1)] {
+        assert_eq!(count(&lit(MonoStrategy::default(), 16, rgb)?), 64 * 64, "{rgb:?}");
+        assert_eq!(BinaryColor::from(rgb), BinaryColor::On, "{rgb:?}");
+    }
+    assert_eq!(count(&lit(MonoStrategy::default(), 16, Rgb::BLACK)?), 0);
+    assert_eq!(BinaryColor::from(Rgb::BLACK), BinaryColor::Off);
+
+    // Ordered dithers are anchored to the screen so the chunk size
+    // doesn't change the pattern.
+    for mono in [MonoStrategy::Bayer4, MonoStrategy::Bayer8] {
+        let small = lit(mono, 8, gray)?;
+        let large = lit(mono, 16, gray)?;
+        assert!(small.pages == large.pages, "{mono:?}");
+        assert_eq!(count(&large), 64 * 64 / 2, "{mono:?}");
+    }
+
+    let diffused = count(&lit(MonoStrategy::FloydSteinberg, 16, gray)?);
+    assert!(diffused.abs_diff(64 * 64 / 2) < 64, "{diffused}");
+
+    // Both renderers quantize the same way when given a strategy.
+    let mut display = SimulatorDisplay::<BinaryColor>::new(Size::new(64, 64));
+    let mut renderer = embedded_render::EmbeddedRender::new(&mut display, 16, 16);
+    renderer.set_mono_strategy(Some(MonoStrategy::Bayer8));
+    let mut commands = DisplayList::<1>::new();
+    commands.set(0, Command::new_rect(area, gray))?;
+    smol::block_on(commands.draw(&mut renderer))?;
+
+    let mock = lit(MonoStrategy::Bayer8, 16, gray)?;
+    for x in 0..64 {
+        for y in 0..64 {
+            let expected = display.get_pixel(EgPoint::new(x as i32, y as i32)) == BinaryColor::On;
+            assert_eq!(mock.get_pixel(x, y), expected, "pixel {x}, {y}");
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn translucent_commands_blend_with_what_is_beneath() -> Result<(), DisplayListError> {
+    let white = Rgb::from_rgb(255, 255, 255);
+    let red = Rgb { r: 200, g: 0, b: 0 };
+    let screen = BoundingBox::new(0, 0, 32, 32);
+
+    let backdrop = Command::new_translucent_rect(screen, Rgba::new(0, 0, 0, 128));
+    assert!(!backdrop.is_opaque());
+    assert_eq!(backdrop.covers(&BoundingBox::new(0, 0, 16, 16)), Ok(false));
+
+    let mut display = SimulatorDisplay::<Rgb888>::new(Size::new(32, 32));
+    let mut renderer = embedded_render::EmbeddedRender::new(&mut display, 16, 16);
+    let mut commands = DisplayList::<4>::new();
+
+    commands.set(0, Command::new_rect(BoundingBox::new(0, 0, 16, 32), white))?;
+    commands.set(1, Command::new_rect(BoundingBox::new(16, 0, 32, 32), red))?;
+    commands.set(2, backdrop)?;
+    commands.set(3, Command::new_rect(BoundingBox::new(8, 8, 24, 24), white).with_blend(BlendMode::Xor))?;
+    smol::block_on(commands.draw(&mut renderer))?;
+
+    // The backdrop dims both halves and the XOR flips the result.
+    let pixel = |display: &SimulatorDisplay<Rgb888>, x, y| display.get_pixel(EgPoint::new(x, y));
+    let display = renderer.get_display();
+    assert_eq!(pixel(display, 2, 2), Rgb888::new(127, 127, 127));
+    assert_eq!(pixel(display, 30, 2), Rgb888::new(100, 0, 0));
+    assert_eq!(pixel(display, 10, 10), Rgb888::new(128, 128, 128));
+    assert_eq!(pixel(display, 20, 20), Rgb888::new(155, 255, 255));
+
+    // Changing only the opacity still redraws.
+    commands.update(2, backdrop.with_opacity(255))?;
+    smol::block_on(commands.draw(&mut renderer))?;
+    assert_eq!(pixel(renderer.get_display(), 2, 2), Rgb888::new(0, 0, 0));
+
+    let gray = Rgb::from_rgb(100, 100, 100);
+    assert_eq!(BlendMode::Multiply.blend(255, gray, white), gray);
+    assert_eq!(BlendMode::Additive.blend(255, gray, gray), Rgb::from_rgb(200, 200, 200));
+    assert_eq!(BlendMode::Additive.blend(0, white, gray), gray);
+
+    Ok(())
+}
+
+#[test]
+fn moving_highlight_restores_the_text_beneath() -> Result<(), DisplayListError> {
+    use embedded_graphics::mono_font::ascii::FONT_6X10;
+
+    let white = Rgb::from_rgb(255, 255, 255);
+    let menu = [
+        Command::new_text(Point::new(2, 2), "Settings", &FONT_6X10, white)?,
+        Command::new_text(Point::new(2, 14), "About", &FONT_6X10, white)?,
+    ];
+    let first = Command::new_inverted_rect(BoundingBox::new(0, 0, 64, 12));
+    let second = Command::new_inverted_rect(BoundingBox::new(0, 12, 64, 24));
+
+    let mut mock = MockSh1107::new();
+    let mut sh1107 = sh1107_render::Sh1107Render::<_, 32>::new(&mut mock, 64, 64, 16, 16);
+    let mut commands = DisplayList::<3>::new();
+    commands.set(0, menu[0])?;
+    commands.set(1, menu[1])?;
+    commands.set(2, first)?;
+    smol::block_on(commands.draw(&mut sh1107))?;
+
+    // The bar is lit where the text isn't.
+    assert!(mock.get_pixel(0, 0));
+
+    let mut sh1107 = sh1107_render::Sh1107Render::<_, 32>::new(&mut mock, 64, 64, 16, 16);
+    commands.update(2, second)?;
+    smol::block_on(commands.draw(&mut sh1107))?;
+
+    let mut fresh = MockSh1107::new();
+    let mut sh1107 = sh1107_render::Sh1107Render::<_, 32>::new(&mut fresh, 64, 64, 16, 16);
+    let mut commands = DisplayList::<3>::new();
+    commands.set(0, menu[0])?;
+    commands.set(1, menu[1])?;
+    commands.set(2, second)?;
+    smol::block_on(commands.draw(&mut sh1107))?;
+
+    assert!(!mock.get_pixel(0, 0));
+    assert!(mock.get_pixel(0, 12));
+    assert!(mock.pages == fresh.pages);
+
+    // Where two bars overlap they cancel out, in both renderers.
+    assert_renderers_agree(&[menu[0], menu[1], second, Command::new_inverted_rect(BoundingBox::new(8, 16, 40, 40))])
+}
+
+#[test]
+fn display_lists_use_the_panel_color() -> Result<(), DisplayListError> {
+    use core::mem::size_of;
+    use embedded_graphics::pixelcolor::{BinaryColor, Gray4};
+
+    // Every command carries a few colors, so narrower ones make the
+    // whole list smaller.
+    assert!(size_of::<Command<BinaryColor>>() < size_of::<Command>());
+    assert!(size_of::<Command<Gray4>>() < size_of::<Command>());
+    // The largest is a polygon, whose points are stored inline.
+    assert!(size_of::<Command>() <= 10 * size_of::<usize>());
+
+    // Besides its commands, a list sized for its display only keeps a
+    // few words per slot.
+    type Panel = DisplayList<64, Rgb, { tile_words(128, 128, 16, 16) }>;
+    assert!(size_of::<Panel>() < 64 * (2 * size_of::<Command>() + 6 * size_of::<usize>()));
+    // Which is what the default is sized for.
+    assert_eq!(size_of::<DisplayList<64>>(), size_of::<Panel>());
+
+    // A Gray4 panel gets its own levels without going through Rgb.
+    let mut display = SimulatorDisplay::<Gray4>::new(Size::new(64, 16));
+    let mut renderer = embedded_render::EmbeddedRender::new(&mut display, 16, 16);
+    let mut commands = DisplayList::<2, Gray4>::new();
+    let shade = Paint::LinearGradient {
+        start: ShortPoint::new(0, 0),
+        end: ShortPoint::new(64, 0),
+        from: Gray4::BLACK,
+        to: Gray4::WHITE,
+    };
+    commands.set(0, Command::new_rect(BoundingBox::new(0, 0, 64, 8), shade))?;
+    commands.set(1, Command::new_line(Point::new(0, 12), Point::new(63, 12), 1, Gray4::new(5)))?;
+    smol::block_on(commands.draw(&mut renderer))?;
+
+    let display = renderer.get_display();
+    let mut levels = [false; 16];
+    for x in 0..64 {
+        levels[display.get_pixel(EgPoint::new(x, 4)).luma() as usize] = true;
+    }
+    assert_eq!(levels.iter().filter(|&&level| level).count(), 16);
+    assert_eq!(display.get_pixel(EgPoint::new(30, 12)), Gray4::new(5));
+
+    // The same BinaryColor list draws on both renderers.
+    let on = BinaryColor::On;
+    let list = [
+        Command::new_circle(Point::new(4, 4), 40, ShapeStyle::stroke(on, 3)),
+        Command::new_rect(BoundingBox::new(30, 30, 60, 50), on),
+        Command::new_inverted_rect(BoundingBox::new(20, 20, 40, 40)),
+    ];
+
+    assert_renderers_agree(&list)
+}
+
+#[test]
+fn named_colors_and_palettes() -> Result<(), DisplayListError> {
+    use core::mem::size_of;
+
+    // Green is the second argument.
+    assert_eq!(Rgb::from_rgb(1, 2, 3).g(), 2);
+
+    // The old constructor still takes blue before green.
+    #[allow(deprecated)]
+    let old = Rgb::new(1, 2, 3);
+    assert_eq!(old, Rgb::from_rgb(1, 3, 2));
+    assert_eq!(Rgb::from_hex(0x00FF00), Rgb::GREEN);
+    assert_eq!(Rgb::from_rgb565(0xF800), Rgb::RED);
+    assert_eq!(Rgb::from_rgb565(0xFFFF), Rgb::WHITE);
+    assert_eq!(Rgb::from_hsv(0, 255, 255), Rgb::RED);
+    assert_eq!(Rgb::from_hsv(240, 255, 255), Rgb::BLUE);
+    assert_eq!(Rgb::from_hsv(60, 255, 255), Rgb::YELLOW);
+    assert_eq!(Rgb::from_hsv(90, 0, 200), Rgb::from_rgb(200, 200, 200));
+    assert_eq!(Rgb::WHITE.luminance(), 255);
+    assert!(Rgb::GREEN.luminance() > Rgb::RED.luminance());
+
+    #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+    struct Ui;
+
+    impl Palette for Ui {
+        const COLORS: [Rgb; PALETTE_SIZE] = [
+            Rgb::BLACK, Rgb::WHITE, Rgb::RED, Rgb::GREEN,
+            Rgb::BLUE, Rgb::YELLOW, Rgb::CYAN, Rgb::MAGENTA,
+            Rgb::GRAY, Rgb::from_hex(0x404040), Rgb::from_hex(0xC0C0C0), Rgb::from_hex(0xFF8000),
+            Rgb::from_hex(0x800000), Rgb::from_hex(0x008000), Rgb::from_hex(0x000080), Rgb::from_hex(0x808000),
+        ];
+    }
+
+    // An index is one byte, so an indexed list is smaller than an Rgb one.
+    assert!(size_of::<Command<Indexed<Ui>>>() < size_of::<Command>());
+    assert!(size_of::<DisplayList<8, Indexed<Ui>>>() < size_of::<DisplayList<8>>());
+    assert_eq!(Indexed::<Ui>::from(Rgb::from_rgb(250, 10, 5)), Indexed::new(2));
+
+    // The renderer resolves indices when it draws.
+    let mut display = SimulatorDisplay::<Rgb888>::new(Size::new(32, 16));
+    let mut renderer = embedded_render::EmbeddedRender::new(&mut display, 16, 16);
+    let mut commands = DisplayList::<2, Indexed<Ui>>::new();
+    commands.set(0, Command::new_rect(BoundingBox::new(0, 0, 32, 16), Indexed::new(11)))?;
+    commands.set(1, Command::new_line(Point::new(0, 8), Point::new(31, 8), 1, Indexed::new(6)))?;
+    smol::block_on(commands.draw(&mut renderer))?;
+
+    let display = renderer.get_display();
+    assert_eq!(display.get_pixel(EgPoint::new(3, 3)), Rgb888::new(255, 128, 0));
+    assert_eq!(display.get_pixel(EgPoint::new(20, 8)), Rgb888::new(0, 255, 255));
+
+    Ok(())
+}
+
+#[test]
+fn reordering_only_repaints_where_commands_overlap() -> Result<(), DisplayListError> {
+    let red = Rgb::RED;
+    let blue = Rgb::BLUE;
+    let a = Command::new_rect(BoundingBox::new(0, 0, 24, 24), red);
+    let b = Command::new_rect(BoundingBox::new(16, 16, 40, 40), blue);
+
+    let mut renderer = CountingRenderer::new(64, 64, 8);
+    let mut commands = DisplayList::<4>::new();
+    let first = commands.push(a)?;
+    let second = commands.push(b)?;
+    smol::block_on(commands.draw(&mut renderer))?;
+
+    renderer.flushes = 0;
+    commands.raise(first)?;
+    assert_eq!(commands.position(first)?, 3);
+    smol::block_on(commands.draw(&mut renderer))?;
+
+    let mut overlap = 0;
+    for x in (0..64).step_by(8) {
+        for y in (0..64).step_by(8) {
+            let tile = BoundingBox::new(x, y, x + 8, y + 8);
+            if a.intersects(&tile)? && b.intersects(&tile)? {
+                overlap += 1;
+            }
+        }
+    }
+    assert_eq!(renderer.flushes, overlap);
+
+    // Nothing changed so nothing is drawn.
+    renderer.flushes = 0;
+    smol::block_on(commands.draw(&mut renderer))?;
+    assert_eq!(renderer.flushes, 0);
+
+    let green = Command::new_rect(BoundingBox::new(8, 8, 32, 32), Rgb::GREEN);
+    let middle = commands.insert_below(first, green)?;
+    let bottom = commands.insert_below(second, green)?;
+    let order = [bottom, second, middle, first].map(|handle| commands.position(handle));
+    assert_eq!(order, [Ok(0), Ok(1), Ok(2), Ok(3)]);
+    assert_eq!(commands.insert_above(first, green), Err(DisplayListError::Full));
+
+    commands.remove(bottom)?;
+    let top = commands.insert_above(first, green)?;
+    assert_eq!(commands.position(top)?, 3);
+
+    // The new command took the removed one's slot, but not its handle.
+    assert_eq!(top.index(), bottom.index());
+    assert_eq!(commands.position(bottom), Err(DisplayListError::StaleHandle));
+    assert_eq!(commands.remove(bottom), Err(DisplayListError::StaleHandle));
+    assert_eq!(commands.raise(bottom), Err(DisplayListError::StaleHandle));
+
+    commands.move_to(top, 0)?;
+    let order = [top, second, middle, first].map(|handle| commands.position(handle));
+    assert_eq!(order, [Ok(0), Ok(1), Ok(2), Ok(3)]);
+
+    // The same on screen as a list drawn from scratch in that order.
+    let mut display = SimulatorDisplay::<Rgb888>::new(Size::new(64, 64));
+    let mut renderer = embedded_render::EmbeddedRender::new(&mut display, 8, 8);
+    let mut commands = DisplayList::<2>::new();
+    let first = commands.push(a)?;
+    commands.push(b)?;
+    smol::block_on(commands.draw(&mut renderer))?;
+    assert_eq!(renderer.get_display().get_pixel(EgPoint::new(20, 20)), Rgb888::new(0, 0, 255));
+
+    commands.raise(first)?;
+    smol::block_on(commands.draw(&mut renderer))?;
+    let display = renderer.get_display();
+    assert_eq!(display.get_pixel(EgPoint::new(20, 20)), Rgb888::new(255, 0, 0));
+    assert_eq!(display.get_pixel(EgPoint::new(30, 30)), Rgb888::new(0, 0, 255));
+
+    Ok(())
+}
+
+#[test]
+fn removed_and_hidden_commands_are_painted_over() -> Result<(), DisplayListError> {
+    let panel = Command::new_rect(BoundingBox::new(0, 0, 64, 64), Rgb::BLUE);
+    let button = Command::new_rect(BoundingBox::new(4, 4, 20, 12), Rgb::RED);
+    let badge = Command::new_circle(Point::new(40, 40), 12, ShapeStyle::fill(Rgb::GREEN));
+
+    let mut display = SimulatorDisplay::<Rgb888>::new(Size::new(64, 64));
+    let mut renderer = embedded_render::EmbeddedRender::new(&mut display, 8, 8);
+    let mut commands = DisplayList::<3>::new();
+    commands.set(0, panel)?;
+    commands.set(1, button)?;
+    commands.set(2, badge)?;
+    smol::block_on(commands.draw(&mut renderer))?;
+
+    commands.remove_at(1)?;
+    commands.set_visible(2, false)?;
+    assert!(!commands.get(2)?.is_visible());
+    assert_eq!(commands.handle(1), Err(DisplayListError::EmptySlot));
+    smol::block_on(commands.draw(&mut renderer))?;
+
+    let blue = Rgb888::new(0, 0, 255);
+    let display = renderer.get_display();
+    assert_eq!(display.get_pixel(EgPoint::new(10, 8)), blue);
+    assert_eq!(display.get_pixel(EgPoint::new(46, 46)), blue);
+
+    // Only the tiles the two used to be in are repainted.
+    let mut counting = CountingRenderer::new(64, 64, 8);
+    let mut commands = DisplayList::<3>::new();
+    commands.set(0, panel)?;
+    commands.set(1, button)?;
+    commands.set(2, badge)?;
+    smol::block_on(commands.draw(&mut counting))?;
+
+    counting.flushes = 0;
+    commands.remove(commands.handle(1)?)?;
+    commands.set_visible(2, false)?;
+    smol::block_on(commands.draw(&mut counting))?;
+
+    let mut expected = 0;
+    for x in (0..64).step_by(8) {
+        for y in (0..64).step_by(8) {
+            let tile = BoundingBox::new(x, y, x + 8, y + 8);
+            if button.intersects(&tile)? || badge.intersects(&tile)? {
+                expected += 1;
+            }
+        }
+    }
+    assert_eq!(counting.flushes, expected);
+
+    // Showing it again brings it back where it was.
+    counting.flushes = 0;
+    commands.set_visible(2, true)?;
+    smol::block_on(commands.draw(&mut counting))?;
+    assert!(counting.flushes > 0 && counting.flushes < expected);
+
+    Ok(())
+}
+
+#[test]
+fn updates_can_change_a_commands_flavor() -> Result<(), DisplayListError> {
+    use embedded_graphics::mono_font::ascii::FONT_6X10;
+
+    static ICON: [u8; 8] = [0xFF; 8];
+    static ICON_IMAGE: ImageData = image(ImageData::new(&ICON, ImageFormat::Mono, 8, 8));
+
+    let icon = Command::new_image(Point::new(2, 2), &ICON_IMAGE);
+    let label = Command::new_text(Point::new(30, 40), "OK", &FONT_6X10, Rgb::WHITE)?;
+
+    let mut counting = CountingRenderer::new(64, 64, 8);
+    let mut commands = DisplayList::<1>::new();
+    commands.set(0, icon)?;
+    smol::block_on(commands.draw(&mut counting))?;
+
+    counting.flushes = 0;
+    commands.update(0, label)?;
+    smol::block_on(commands.draw(&mut counting))?;
+
+    // The icon's tile and the label's.
+    let mut expected = 0;
+    for x in (0..64).step_by(8) {
+        for y in (0..64).step_by(8) {
+            let tile = BoundingBox::new(x, y, x + 8, y + 8);
+            if icon.intersects(&tile)? || label.intersects(&tile)? {
+                expected += 1;
+            }
+        }
+    }
+    assert_eq!(counting.flushes, expected);
+
+    let mut mock = MockSh1107::new();
+    let mut sh1107 = sh1107_render::Sh1107Render::<_, 32>::new(&mut mock, 64, 64, 16, 16);
+    let mut commands = DisplayList::<1>::new();
+    commands.set(0, icon)?;
+    smol::block_on(commands.draw(&mut sh1107))?;
+    commands.update(0, label)?;
+    smol::block_on(commands.draw(&mut sh1107))?;
+    assert!(!mock.get_pixel(4, 4));
+
+    commands.set_strict_updates(true);
+    assert_eq!(
+        commands.update(0, icon),
+        Err(DisplayListError::UpdateFlavorMismatch("Text", "Image"))
+    );
+
+    Ok(())
+}
+
+/// A small xorshift so the long running tests are repeatable.
+struct Random(u32);
+
+impl Random {
+    fn next(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+
+    fn below(&mut self, n: u32) -> u32 {
+        self.next() % n
+    }
+
+    fn command(&mut self) -> Command {
+        // Some start off the top or left of the screen.
+        let x1 = self.below(68) as i32 - 8;
+        let y1 = self.below(68) as i32 - 8;
+        let bounds = BoundingBox::new(x1, y1, x1 + 1 + self.below((64 - x1) as u32) as i32, y1 + 1 + self.below((64 - y1) as u32) as i32);
+        let color = [Rgb::BLACK, Rgb::WHITE, Rgb::GRAY][self.below(3) as usize];
+
+        match self.below(5) {
+            0 | 1 => Command::new_rect(bounds, color),
+            2 => Command::new_circle(Point::new(x1, y1), 1 + self.below(16), ShapeStyle::fill(color)),
+            3 => Command::new_inverted_rect(bounds),
+            _ => Command::new_translucent_rect(bounds, Rgba::new(255, 255, 255, self.below(256) as u8)),
+        }
+    }
+}
+
+/// Check `display` shows what drawing `commands` from scratch would.
+fn assert_shows<const SLOTS: usize>(
+    commands: &DisplayList<SLOTS>,
+    display: &SimulatorDisplay<embedded_graphics::pixelcolor::BinaryColor>,
+    frame: usize,
+) -> Result<(), DisplayListError> {
+    use embedded_graphics::pixelcolor::BinaryColor;
+
+    // The same commands in the same order.
+    let mut expected = SimulatorDisplay::<BinaryColor>::new(display.size());
+    let mut fresh = embedded_render::EmbeddedRender::new(&mut expected, 8, 8);
+    let mut reference = DisplayList::<SLOTS>::new();
+    for slot in 0..SLOTS {
+        match commands.handle(slot) {
+            Ok(handle) => reference.set(commands.position(handle)?, commands.get(slot)?)?,
+            Err(DisplayListError::EmptySlot) => (),
+            Err(error) => return Err(error),
+        }
+    }
+    smol::block_on(reference.draw(&mut fresh))?;
+
+    for point in display.bounding_box().points() {
+        assert_eq!(display.get_pixel(point), expected.get_pixel(point), "frame {frame}, pixel {point}");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn xor_stacks_look_the_same_on_both_renderers() -> Result<(), DisplayListError> {
+    // Xor flips the pixel beneath, not each channel of its color.
+    let screen = BoundingBox::new(0, 0, 64, 64);
+    assert_renderers_agree(&[
+        Command::new_rect(screen, Rgb::GRAY),
+        Command::new_rect(screen, Rgb::WHITE).with_blend(BlendMode::Xor),
+        Command::new_rect(screen, Rgb::GREEN).with_blend(BlendMode::Xor),
+    ])?;
+
+    let mut random = Random(0x2545_F491);
+    for _ in 0..50 {
+        let mut commands = [Command::null(); 6];
+        for command in commands.iter_mut() {
+            *command = random.command();
+            if random.below(2) == 0 {
+                let color = [Rgb::BLACK, Rgb::WHITE, Rgb::GRAY, Rgb::GREEN, Rgb::from_rgb(0, 0, 1)];
+                let color = color[random.below(5) as usize];
+                *command = Command::new_rect(command.bounds, color).with_blend(BlendMode::Xor);
+            }
+        }
+        assert_renderers_agree(&commands)?;
+    }
+
+    Ok(())
+}
+
+#[test]
+fn thousands_of_incremental_frames_match_a_full_redraw() -> Result<(), DisplayListError> {
+    use embedded_graphics::pixelcolor::BinaryColor;
+
+    const SLOTS: usize = 6;
+
+    let mut random = Random(0x2545_F491);
+    let mut display = SimulatorDisplay::<BinaryColor>::new(Size::new(64, 64));
+    let mut renderer = embedded_render::EmbeddedRender::new(&mut display, 8, 8);
+    let mut commands = DisplayList::<SLOTS>::new();
+
+    // Never changed again, so it outlives any wrapping counter.
+    commands.set(0, Command::new_rect(BoundingBox::new(8, 8, 40, 40), Rgb::WHITE))?;
+
+    for frame in 0..3000 {
+        // Several changes between some frames and none between others.
+        for _ in 0..random.below(3) {
+            let slot = 1 + random.below(SLOTS as u32 - 1) as usize;
+            // Empty slots have no handle to move or remove.
+            let handle = commands.handle(slot);
+            match (random.below(8), handle) {
+                (0 | 1, _) => commands.set(slot, random.command())?,
+                (2, _) => commands.update(slot, random.command())?,
+                (3 | 5 | 6, Err(DisplayListError::EmptySlot)) => (),
+                (3, handle) => commands.remove(handle?)?,
+                (4, _) => commands.set_visible(slot, random.below(2) == 0)?,
+                (5, handle) => commands.raise(handle?)?,
+                (6, handle) => commands.lower(handle?)?,
+                (_, _) => match commands.push(random.command()) {
+                    Ok(_) | Err(DisplayListError::Full) => (),
+                    Err(error) => return Err(error),
+                },
+            }
+        }
+
+        smol::block_on(commands.draw(&mut renderer))?;
+
+        assert_shows(&commands, renderer.get_display(), frame)?;
+    }
+
+    Ok(())
+}
+
+#[test]
+fn failed_draws_heal_on_the_next_frame() -> Result<(), DisplayListError> {
+    let background = Command::new_rect(BoundingBox::new(0, 0, 64, 64), Rgb::WHITE);
+    let cursor = Command::new_inverted_rect(BoundingBox::new(10, 20, 50, 28));
+
+    // Only the tiles from the one that failed onwards are painted again.
+    let mut counting = CountingRenderer::new(64, 64, 8);
+    let mut commands = DisplayList::<2>::new();
+    commands.set(0, background)?;
+    smol::block_on(commands.draw(&mut counting))?;
+
+    counting.flushes = 0;
+    counting.fail_after = Some(10);
+    commands.update(0, Command::new_rect(BoundingBox::new(0, 0, 64, 64), Rgb::GRAY))?;
+    assert_eq!(
+        smol::block_on(commands.draw(&mut counting)),
+        Err(DisplayListError::RenderError(RendererError::BackingError))
+    );
+    assert_eq!(counting.flushes, 10);
+
+    smol::block_on(commands.draw(&mut counting))?;
+    assert_eq!(counting.flushes, 64);
+
+    counting.flushes = 0;
+    smol::block_on(commands.draw(&mut counting))?;
+    assert_eq!(counting.flushes, 0);
+
+    // A tile torn by a failed flush is painted again even if the
+    // command goes back to what it was before the retry.
+    let mut mock = MockSh1107::new();
+    let mut sh1107 = sh1107_render::Sh1107Render::<_, 32>::new(&mut mock, 64, 64, 16, 16);
+    let mut commands = DisplayList::<1>::new();
+    commands.set(0, background)?;
+    smol::block_on(commands.draw(&mut sh1107))?;
+
+    sh1107.get_display_mut().nak_after = Some(1);
+    commands.update(0, Command::new_rect(BoundingBox::new(0, 0, 64, 64), Rgb::BLACK))?;
+    assert!(smol::block_on(commands.draw(&mut sh1107)).is_err());
+    sh1107.get_display_mut().nak_after = None;
+    commands.update(0, background)?;
+    smol::block_on(commands.draw(&mut sh1107))?;
+    assert!(mock.get_pixel(0, 0));
+
+    // A panel that NAKs now and then still ends up showing the list,
+    // even when it changes again before the retry.
+    let mut random = Random(0x9E37_79B9);
+    let mut mock = MockSh1107::new();
+    let mut sh1107 = sh1107_render::Sh1107Render::<_, 32>::new(&mut mock, 64, 64, 16, 16);
+    let mut commands = DisplayList::<2>::new();
+    commands.set(0, background)?;
+    commands.set(1, cursor)?;
+
+    for _ in 0..200 {
+        if random.below(2) == 0 {
+            let y = random.below(56) as i32;
+            commands.update(1, Command::new_inverted_rect(BoundingBox::new(10, y, 50, y + 8)))?;
+        }
+
+        sh1107.get_display_mut().nak_after = match random.below(3) {
+            0 => Some(random.below(40) as usize),
+            _ => None,
+        };
+        match smol::block_on(commands.draw(&mut sh1107)) {
+            Ok(_) | Err(DisplayListError::RenderError(_)) => (),
+            Err(error) => return Err(error),
+        }
+    }
+    sh1107.get_display_mut().nak_after = None;
+    smol::block_on(commands.draw(&mut sh1107))?;
+
+    let mut expected = MockSh1107::new();
+    let mut fresh = sh1107_render::Sh1107Render::<_, 32>::new(&mut expected, 64, 64, 16, 16);
+    let mut reference = DisplayList::<2>::new();
+    reference.set(0, background)?;
+    reference.set(1, commands.get(1)?)?;
+    smol::block_on(reference.draw(&mut fresh))?;
+
+    assert!(mock.pages == expected.pages);
+
+    Ok(())
+}
+
+#[test]
+fn drawing_in_steps_keeps_each_frame_consistent() -> Result<(), DisplayListError> {
+    use embedded_graphics::pixelcolor::BinaryColor;
+
+    const SLOTS: usize = 4;
+
+    let background = Command::new_rect(BoundingBox::new(0, 0, 64, 64), Rgb::WHITE);
+
+    // At most the budget is painted each step.
+    let mut counting = CountingRenderer::new(64, 64, 8);
+    let mut commands = DisplayList::<SLOTS>::new();
+    commands.set(0, background)?;
+    assert_eq!(smol::block_on(commands.draw_step(&mut counting, 10))?, Some(10));
+    assert_eq!(counting.flushes, 10);
+    assert_eq!(smol::block_on(commands.draw_step(&mut counting, 50))?, Some(60));
+    assert_eq!(smol::block_on(commands.draw_step(&mut counting, 50))?, None);
+    assert_eq!(counting.flushes, 64);
+
+    // Only changed tiles count towards it.
+    counting.flushes = 0;
+    commands.set(1, Command::new_rect(BoundingBox::new(0, 0, 6, 64), Rgb::BLACK))?;
+    assert_eq!(smol::block_on(commands.draw_step(&mut counting, 100))?, None);
+    assert_eq!(counting.flushes, 8);
+
+    // Changes between steps, including to tiles already drawn this
+    // frame, always end up on screen.
+    let mut random = Random(0x1B87_3593);
+    let mut display = SimulatorDisplay::<BinaryColor>::new(Size::new(64, 64));
+    let mut renderer = embedded_render::EmbeddedRender::new(&mut display, 8, 8);
+    let mut commands = DisplayList::<SLOTS>::new();
+    commands.set(0, background)?;
+
+    for frame in 0..500 {
+        if random.below(2) == 0 {
+            let slot = 1 + random.below(SLOTS as u32 - 1) as usize;
+            commands.update(slot, random.command())?;
+        }
+
+        if random.below(4) == 0 {
+            smol::block_on(commands.draw(&mut renderer))?;
+            assert_shows(&commands, renderer.get_display(), frame)?;
+        } else {
+            smol::block_on(commands.draw_step(&mut renderer, 1 + random.below(4) as usize))?;
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn nothing_changed_means_no_renderer_calls() -> Result<(), DisplayListError> {
+    let background = Command::new_rect(BoundingBox::new(0, 0, 64, 64), Rgb::BLACK);
+    let dot = Command::new_rect(BoundingBox::new(20, 20, 23, 23), Rgb::WHITE);
+
+    let mut counting = CountingRenderer::new(64, 64, 8);
+    let mut commands = DisplayList::<2, Rgb, { tile_words(64, 64, 8, 8) }>::new();
+    commands.set(0, background)?;
+    commands.set(1, dot)?;
+    assert_eq!(smol::block_on(commands.draw(&mut counting))?, 64);
+
+    counting.calls.set(0);
+    assert_eq!(smol::block_on(commands.draw(&mut counting))?, 0);
+    assert_eq!(counting.calls.get(), 0);
+
+    // Setting a command to what it already is marks its tiles, which
+    // are looked at and found to be the same.
+    commands.set(1, dot)?;
+    assert_eq!(smol::block_on(commands.draw(&mut counting))?, 0);
+    assert_eq!(smol::block_on(commands.draw_step(&mut counting, 1))?, None);
+
+    // Moving the dot paints where it was and where it is.
+    commands.update(1, Command::new_rect(BoundingBox::new(41, 41, 44, 44), Rgb::WHITE))?;
+    assert_eq!(smol::block_on(commands.draw(&mut counting))?, 2);
+
+    commands.lower(commands.handle(1)?)?;
+    assert_eq!(smol::block_on(commands.draw(&mut counting))?, 1);
+
+    // Without enough bits every tile is looked at, which is slower
+    // but paints the same.
+    let mut small = DisplayList::<2, Rgb, 1>::new();
+    small.set(0, background)?;
+    small.set(1, dot)?;
+    smol::block_on(small.draw(&mut counting))?;
+    small.update(1, Command::new_rect(BoundingBox::new(41, 41, 44, 44), Rgb::WHITE))?;
+    assert_eq!(smol::block_on(small.draw(&mut counting))?, 2);
+
+    Ok(())
+}
+
+#[test]
+fn commands_that_cover_a_tile_together_hide_what_is_beneath() -> Result<(), DisplayListError> {
+    let background = Command::new_rect(BoundingBox::new(0, 0, 64, 64), Rgb::BLUE);
+    let left = Command::new_rect(BoundingBox::new(0, 0, 36, 64), Rgb::RED);
+    let right = Command::new_circle(Point::new(50, 32), 60, ShapeStyle::fill(Rgb::GREEN));
+
+    let mut counting = CountingRenderer::new(64, 64, 8);
+    let mut commands = DisplayList::<3>::new();
+    commands.set(0, background)?;
+    commands.set(1, left)?;
+    commands.set(2, Command::new_rect(BoundingBox::new(36, 0, 64, 64), Rgb::GREEN))?;
+    smol::block_on(commands.draw(&mut counting))?;
+
+    // The tiles the two halves meet in draw both and nothing else.
+    assert_eq!(counting.clears, 0);
+    assert_eq!(counting.draws, 8 * (7 + 2));
+
+    // The same with a shape instead, which paints over every pixel
+    // of most of the tiles it overlaps.
+    assert_renderers_agree(&[background, left, right])?;
+
+    // Something translucent on top still needs what is beneath it.
+    let mut commands = DisplayList::<2>::new();
+    commands.set(0, background)?;
+    commands.set(1, Command::new_translucent_rect(BoundingBox::new(0, 0, 64, 64), Rgba::new(255, 255, 255, 128)))?;
+    let mut counting = CountingRenderer::new(64, 64, 8);
+    smol::block_on(commands.draw(&mut counting))?;
+    assert_eq!(counting.clears, 0);
+    assert_eq!(counting.draws, 64 * 2);
+
+    Ok(())
+}
+
+#[test]
+fn bounding_boxes_are_half_open() {
+    let a = BoundingBox::new(2, 3, 10, 7);
+    let b = BoundingBox::new(8, 5, 20, 30);
+    let apart = BoundingBox::new(10, 3, 12, 7);
+
+    assert_eq!((a.width(), a.height()), (8, 4));
+    assert!(a.contains(Point::new(2, 3)) && a.contains(Point::new(9, 6)));
+    assert!(!a.contains(Point::new(10, 6)) && !a.contains(Point::new(9, 7)));
+
+    assert_eq!(a.intersection(&b), BoundingBox::new(8, 5, 10, 7));
+    assert!(a.intersection(&apart).is_empty());
+    assert_eq!(a.intersection(&apart).width(), 0);
+
+    assert_eq!(a.union(&b), BoundingBox::new(2, 3, 20, 30));
+    assert_eq!(a.union(&BoundingBox::new(50, 50, 50, 60)), a);
+    assert!(BoundingBox::new(5, 5, 5, 9).is_empty());
+}
+
+#[test]
+fn odd_display_sizes_paint_the_last_row_and_column() -> Result<(), DisplayListError> {
+    let (width, height) = (61, 37);
+    let screen = BoundingBox::new(0, 0, width, height);
+    let corner = Command::new_rect(BoundingBox::new(width - 1, height - 1, width, height), Rgb::WHITE);
+
+    // Every tile, the ragged ones on the edges included.
+    let mut counting = CountingRenderer::new(width as u32, height as u32, 8);
+    let mut commands = DisplayList::<2>::new();
+    commands.set(0, Command::new_rect(screen, Rgb::GRAY))?;
+    assert_eq!(smol::block_on(commands.draw(&mut counting))?, 8 * 5);
+
+    // A pixel in the corner is in exactly one tile.
+    commands.set(1, corner)?;
+    assert_eq!(smol::block_on(commands.draw(&mut counting))?, 1);
+
+    let commands = [
+        Command::new_rect(screen, Rgb::WHITE),
+        Command::new_rect(BoundingBox::new(0, height - 1, width, height), Rgb::BLACK),
+        Command::new_circle(Point::new(40, 20), 21, ShapeStyle::fill(Rgb::BLACK)),
+        corner,
+    ];
+
+    let mock = assert_renderers_agree_at(width, height, &commands)?;
+
+    // Only the columns and pages on the display go over the bus.
+    assert_eq!(mock.sent, width as usize * (height as usize).div_ceil(8));
+
+    assert!(mock.get_pixel(width as usize - 1, height as usize - 1));
+    assert!(mock.get_pixel(width as usize - 2, 0));
+    assert!(!mock.get_pixel(0, height as usize - 1));
+
+    Ok(())
+}
+
+#[test]
+fn commands_slide_in_from_off_the_screen() -> Result<(), DisplayListError> {
+    use embedded_graphics::mono_font::ascii::FONT_6X10;
+
+    // Each frame moves everything further onto the screen from the
+    // left and top, so the edges clip every kind of shape.
+    for x in (-48..=8).step_by(4) {
+        let commands = [
+            Command::new_circle(Point::new(x, x), 30, ShapeStyle::fill_and_stroke(Rgb::GRAY, Rgb::WHITE, 3)),
+            Command::new_line(Point::new(x, 40), Point::new(x + 50, 60), 3, Rgb::WHITE),
+            Command::new_text(Point::new(x, 28), "Hi", &FONT_6X10, Rgb::WHITE)?,
+            Command::new_inverted_rect(BoundingBox::new(44, x, 60, x + 16)),
+            Command::new_rect(BoundingBox::new(x, 4, x + 32, 20), Rgb::WHITE),
+        ];
+
+        let mock = assert_renderers_agree_at(64, 64, &commands)?;
+
+        // The rect shows exactly the columns that are on the screen.
+        assert_eq!(mock.get_pixel(0, 10), x <= 0 && x + 32 > 0, "left edge at {x}");
+        if x + 32 > 0 {
+            assert!(mock.get_pixel((x + 31) as usize, 10), "right edge at {x}");
+        }
+        assert!(!mock.get_pixel((x + 32).max(0) as usize, 10), "past the right edge at {x}");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn commands_off_the_screen_cost_nothing() -> Result<(), DisplayListError> {
+    let background = Command::new_rect(BoundingBox::new(0, 0, 64, 64), Rgb::BLACK);
+    let dot = Command::new_rect(BoundingBox::new(2, 2, 5, 5), Rgb::WHITE);
+
+    let mut counting = CountingRenderer::new(64, 64, 8);
+    let mut commands = DisplayList::<3, Rgb, { tile_words(64, 64, 8, 8) }>::new();
+    commands.set(0, background)?;
+    assert_eq!(smol::block_on(commands.draw(&mut counting))?, 64);
+
+    // Above, left of, right of and below the screen.
+    for bounds in [
+        BoundingBox::new(-50, -50, -10, -10),
+        BoundingBox::new(-8, 20, 0, 30),
+        BoundingBox::new(64, 0, 90, 64),
+        BoundingBox::new(10, 70, 20, 80),
+    ] {
+        commands.set(1, Command::new_rect(bounds, Rgb::WHITE))?;
+        counting.calls.set(0);
+        assert_eq!(smol::block_on(commands.draw(&mut counting))?, 0);
+        assert_eq!(counting.calls.get(), 0);
+    }
+
+    // Sliding on paints only the tile it lands in, and sliding back
+    // off only the tile it left.
+    commands.set(2, Command::new_rect(BoundingBox::new(-20, 2, -17, 5), Rgb::WHITE))?;
+    assert_eq!(smol::block_on(commands.draw(&mut counting))?, 0);
+    commands.update(2, dot)?;
+    assert_eq!(smol::block_on(commands.draw(&mut counting))?, 1);
+    commands.update(2, Command::new_rect(BoundingBox::new(-20, 2, -17, 5), Rgb::WHITE))?;
+    assert_eq!(smol::block_on(commands.draw(&mut counting))?, 1);
+
+    Ok(())
+}
+
@@ -0,0 +1,68 @@
+use core::fmt;
+use core::ptr;
+
+use embedded_graphics::mono_font::MonoFont;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::image::GetPixel;
+use embedded_graphics::prelude::OriginDimensions;
+
+use super::*;
+
+/// A monospaced bitmap font. Fonts are compared by address since
+/// `MonoFont` can't be compared itself.
+#[derive(Clone, Copy)]
+pub struct Font(pub &'static MonoFont<'static>);
+
+impl Font {
+    /// The size of `text` set in this font.
+    pub fn measure(&self, text: &str) -> (u32, u32) {
+        let size = self.0.character_size;
+        let count = text.chars().count() as u32;
+
+        if count == 0 {
+            return (0, 0);
+        }
+
+        let width = count * size.width + (count - 1) * self.0.character_spacing;
+        (width, size.height)
+    }
+
+    /// Is the pixel `x`, `y` of the glyph for `c` set?
+    pub(crate) fn glyph_pixel(&self, c: char, x: u32, y: u32) -> bool {
+        let font = self.0;
+        let size = font.character_size;
+        let image_width = font.image.size().width;
+
+        if size.width == 0 || image_width < size.width {
+            return false;
+        }
+
+        let glyphs_per_row = image_width / size.width;
+        let index = font.glyph_mapping.index(c) as u32;
+        let glyph_x = (index % glyphs_per_row) * size.width;
+        let glyph_y = (index / glyphs_per_row) * size.height;
+
+        let point = embedded_graphics::prelude::Point::new(
+            (glyph_x + x) as i32,
+            (glyph_y + y) as i32,
+        );
+
+        font.image.pixel(point) == Some(BinaryColor::On)
+    }
+}
+
+impl PartialEq for Font {
+    fn eq(&self, other: &Self) -> bool {
+        ptr::eq(self.0, other.0)
+    }
+}
+
+impl Eq for Font {}
+
+impl fmt::Debug for Font {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Font")
+            .field(&self.0.character_size)
+            .finish()
+    }
+}